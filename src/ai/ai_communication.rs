@@ -1,149 +1,654 @@
-use crate::ai::ai_thread::AIThread;
+use crate::ai::ai_thread;
+use crate::ai::ai_thread::{AIThread, SearchContext};
+#[cfg(target_arch = "wasm32")]
+use crate::ai::ai_worker::AIWorkerPool;
+use crate::ai::rotation_cache::{RotationCache, SharedRotationCache};
+use crate::ai::types::ai_settings::AISettings;
+use crate::ai::types::eval_value::EvalValue;
+use crate::ai::types::search_progress::SearchProgress;
+use crate::ai::types::search_stats::SearchStats;
+use crate::ai::types::search_tree::SearchNode;
 use crate::ai::types::skill_eval::SkillEval;
+use crate::ai::types::state_summary::StateSummary;
 use crate::game::game_manager::GameManager;
+use instant::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize};
+#[cfg(not(target_arch = "wasm32"))]
 use std::sync::mpsc;
 use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
 use std::thread;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 
-pub struct AICommunicationPacket {
-    new_game: GameManager,
-    skill_name: String,
+// How often a running search reports its progress
+#[cfg(not(target_arch = "wasm32"))]
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// The result of a single AI turn
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AITurn {
+    pub skill_name: String,
+
+    // The line the AI plans to play, starting with the skill it just used
+    pub plan: SkillEval,
+
+    // The best other first skills, sorted from best to worst
+    pub alternatives: Vec<SkillEval>,
+
+    // Every first skill that was considered, sorted from best to worst
+    pub skill_evals: Vec<SkillEval>,
+
+    // The state the skill was picked in
+    pub state: StateSummary,
+
+    // The recorded search tree, if recording was enabled
+    pub tree: Option<SearchNode>,
+
+    pub stats: SearchStats,
+
+    // The rotation constraints that couldn't be met on this turn or in the planned line
+    pub unmet_constraints: Vec<String>,
+
+    // Whether the turn was loaded from the rotation cache instead of searched
+    #[serde(skip)]
+    pub cached: bool,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+pub enum AICommunicationPacket {
+    Progress(SearchProgress),
+    Turn {
+        new_game: GameManager,
+        turn: Box<AITurn>,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub struct AICommunicator {
     pub ai: AI,
+
+    // Every search gets its own channel, so a cancelled search can't send to the next one
     channel_recv: mpsc::Receiver<AICommunicationPacket>,
-    channel_send: mpsc::Sender<AICommunicationPacket>,
+
+    // The last progress report of the running search
+    progress: Option<SearchProgress>,
+
+    // Stops the running search
+    cancelled: Arc<AtomicBool>,
+
+    cache: Option<SharedRotationCache>,
+    cache_key: Option<u64>,
+    cached_turn: Option<AITurn>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl AICommunicator {
-    pub fn new(ai: AI) -> Self {
-        let (tx, rx) = mpsc::channel();
+    pub fn new(ai: AI, cache: Option<SharedRotationCache>) -> Self {
         Self {
             ai,
-            channel_recv: rx,
-            channel_send: tx,
+            channel_recv: mpsc::channel().1,
+            progress: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            cache,
+            cache_key: None,
+            cached_turn: None,
         }
     }
 
-    pub fn do_turn(&self) {
+    pub fn do_turn(&mut self) {
+        if self.find_cached_turn() {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        self.channel_recv = rx;
+        self.cancelled = Arc::new(AtomicBool::new(false));
+
         let mut ai = self.ai.clone();
-        let channel = self.channel_send.clone();
-        thread::spawn(move || ai.do_best_skill(channel));
+        let cancelled = self.cancelled.clone();
+        thread::spawn(move || ai.do_best_skill(tx, cancelled));
+    }
+
+    // Stops the running search, its result is never returned
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.channel_recv = mpsc::channel().1;
+        self.progress = None;
+        self.cache_key = None;
+        self.cached_turn = None;
+    }
+
+    // Cancels the running search, the next turn is searched from `game`
+    pub fn set_position(&mut self, game: GameManager, settings: AISettings) {
+        self.cancel();
+        self.ai.set_position(game, settings);
     }
 
-    pub fn try_get_skill(&mut self) -> Result<String, TryRecvError> {
-        let raw_packet = self.channel_recv.try_recv();
+    pub fn try_get_skill(&mut self) -> Result<AITurn, TryRecvError> {
+        if let Some(turn) = self.cached_turn.take() {
+            return Ok(self.ai.replay_turn(turn));
+        }
+
+        loop {
+            match self.channel_recv.try_recv()? {
+                AICommunicationPacket::Progress(progress) => self.progress = Some(progress),
+                AICommunicationPacket::Turn { new_game, turn } => {
+                    self.ai.game = new_game;
+                    self.progress = None;
+                    self.cache_turn(&turn);
+                    return Ok(*turn);
+                }
+            }
+        }
+    }
+
+    pub fn progress(&self) -> Option<SearchProgress> {
+        self.progress.clone()
+    }
+}
+
+// Nothing can receive the result of the running search anymore, so don't keep the pool busy with it.
+// The web workers are terminated when their pool is dropped instead.
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for AICommunicator {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+// A search that was handed to the workers
+#[cfg(target_arch = "wasm32")]
+struct PendingSearch {
+    skills: Vec<usize>,
+
+    // The depth the workers are searching, the search is done after `AISettings::depth`
+    depth: u8,
+    skill_evals: Vec<SkillEval>,
+    nodes: u64,
 
-        if let Ok(packet) = raw_packet {
-            self.ai.game = packet.new_game;
-            Ok(packet.skill_name)
+    // The best skill of the last finished depth
+    best_skill: Option<(String, EvalValue)>,
+
+    start_time: Instant,
+    depth_start_time: Instant,
+}
+
+#[cfg(target_arch = "wasm32")]
+pub struct AICommunicator {
+    pub ai: AI,
+
+    // `None` if the browser doesn't allow the workers, the search then runs in this thread
+    workers: Option<AIWorkerPool>,
+    search: Option<PendingSearch>,
+
+    cache: Option<SharedRotationCache>,
+    cache_key: Option<u64>,
+    cached_turn: Option<AITurn>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AICommunicator {
+    pub fn new(ai: AI, cache: Option<SharedRotationCache>) -> Self {
+        Self {
+            workers: Self::create_workers(ai.settings.threads),
+            ai,
+            search: None,
+            cache,
+            cache_key: None,
+            cached_turn: None,
+        }
+    }
+
+    fn create_workers(threads: usize) -> Option<AIWorkerPool> {
+        AIWorkerPool::new(threads)
+            .map_err(|error| {
+                web_sys::console::warn_1(&format!("{}, searching without workers", error).into())
+            })
+            .ok()
+    }
+
+    pub fn do_turn(&mut self) {
+        if self.find_cached_turn() {
+            return;
+        }
+
+        self.search = Some(PendingSearch {
+            skills: self.ai.get_available_skills(),
+            depth: 0,
+            skill_evals: Vec::new(),
+            nodes: 0,
+            best_skill: None,
+            start_time: Instant::now(),
+            depth_start_time: Instant::now(),
+        });
+        self.search_next_depth();
+    }
+
+    // Stops the running search, its result is never returned.
+    // Workers finish the skill they're evaluating, but their results are ignored.
+    pub fn cancel(&mut self) {
+        if let Some(workers) = &self.workers {
+            workers.cancel();
+        }
+        self.search = None;
+        self.cache_key = None;
+        self.cached_turn = None;
+    }
+
+    // Cancels the running search, the next turn is searched from `game`
+    pub fn set_position(&mut self, game: GameManager, settings: AISettings) {
+        self.cancel();
+        if settings.threads != self.ai.settings.threads {
+            self.workers = Self::create_workers(settings.threads);
+        }
+        self.ai.set_position(game, settings);
+    }
+
+    // Like the native search, searches one depth at a time so there's always a best skill to report
+    fn search_next_depth(&mut self) {
+        let search = self.search.as_mut().unwrap();
+        search.depth += 1;
+        search.skill_evals.clear();
+        search.nodes = 0;
+        search.depth_start_time = Instant::now();
+
+        let mut settings = self.ai.search_settings();
+        settings.depth = search.depth;
+        // Only the deepest tree is kept, and every worker records its own tree, so they share the node limit
+        settings.record_nodes = if search.depth < self.ai.max_depth() {
+            0
         } else {
-            Err(raw_packet.err().unwrap())
+            settings.record_nodes / search.skills.len().max(1)
+        };
+
+        match &self.workers {
+            Some(workers) => workers.search(&self.ai.game, &settings, search.skills.clone()),
+            // Blocks the page until the depth is searched, but still finishes the turn
+            None => {
+                let context = Arc::new(SearchContext::new(settings, &self.ai.game));
+                for &skill in &search.skills {
+                    let ai_thread = AIThread::new(&self.ai.game, search.depth, context.clone());
+                    search.skill_evals.push(ai_thread.eval_first_skill(skill));
+                }
+                search.nodes = context.nodes.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn try_get_skill(&mut self) -> Result<AITurn, TryRecvError> {
+        if let Some(turn) = self.cached_turn.take() {
+            return Ok(self.ai.replay_turn(turn));
+        }
+
+        let search = self.search.as_mut().ok_or(TryRecvError::Disconnected)?;
+
+        if let Some(workers) = &self.workers {
+            // A failed worker may never answer, so the depth is searched again without the workers
+            if let Some(error) = workers.error() {
+                web_sys::console::warn_1(&format!("{}, searching without workers", error).into());
+                self.workers = None;
+                search.depth -= 1;
+                self.search_next_depth();
+                return Err(TryRecvError::Empty);
+            }
+
+            for response in workers.take_responses() {
+                search.skill_evals.push(response.skill_eval);
+                search.nodes += response.nodes;
+            }
+        }
+
+        if search.skill_evals.len() < search.skills.len() {
+            return Err(TryRecvError::Empty);
+        }
+
+        if search.depth < self.ai.max_depth() {
+            search.best_skill = best_skill(&search.skill_evals);
+            self.search_next_depth();
+            return Err(TryRecvError::Empty);
+        }
+
+        let search = self.search.take().unwrap();
+        let stats = SearchStats {
+            nodes: search.nodes,
+            elapsed: search.depth_start_time.elapsed(),
+        };
+
+        let turn = self.ai.apply_search(search.skill_evals, stats);
+        self.cache_turn(&turn);
+
+        Ok(turn)
+    }
+
+    pub fn progress(&self) -> Option<SearchProgress> {
+        self.search.as_ref().map(|search| SearchProgress {
+            depth: search.depth,
+            max_depth: self.ai.max_depth(),
+            nodes: search.nodes,
+            elapsed: search.start_time.elapsed(),
+            best_skill: search.best_skill.clone(),
+            skills_done: search.skill_evals.len(),
+            skills_total: search.skills.len(),
+        })
+    }
+}
+
+// Used by both the native and the web `AICommunicator`
+impl AICommunicator {
+    // Looks the current position up in the cache, returns whether it was found
+    fn find_cached_turn(&mut self) -> bool {
+        let Some(cache) = &self.cache else {
+            return false;
+        };
+
+        let key = self.ai.cache_key();
+        self.cached_turn = cache.borrow().get(key);
+        self.cache_key = Some(key);
+
+        self.cached_turn.is_some()
+    }
+
+    fn cache_turn(&mut self, turn: &AITurn) {
+        if let (Some(cache), Some(key)) = (&self.cache, self.cache_key.take()) {
+            cache.borrow_mut().insert(key, turn);
         }
     }
 }
 
+// Progress of the first skills of a running search, shared with the thread reporting it
+#[cfg(not(target_arch = "wasm32"))]
+struct RootProgress {
+    depth: AtomicU8,
+    skills_done: AtomicUsize,
+    skills_total: usize,
+    best_skill: Mutex<Option<(String, EvalValue)>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RootProgress {
+    fn new(skills_total: usize) -> Self {
+        Self {
+            depth: AtomicU8::new(0),
+            skills_done: AtomicUsize::new(0),
+            skills_total,
+            best_skill: Mutex::new(None),
+        }
+    }
+
+    fn start_depth(&self, depth: u8) {
+        self.depth.store(depth, Ordering::Relaxed);
+        self.skills_done.store(0, Ordering::Relaxed);
+    }
+
+    fn report(&self, context: &SearchContext, start_time: Instant) -> SearchProgress {
+        SearchProgress {
+            depth: self.depth.load(Ordering::Relaxed),
+            max_depth: context.settings.depth.max(1),
+            nodes: context.nodes.load(Ordering::Relaxed),
+            elapsed: start_time.elapsed(),
+            best_skill: self.best_skill.lock().unwrap().clone(),
+            skills_done: self.skills_done.load(Ordering::Relaxed),
+            skills_total: self.skills_total,
+        }
+    }
+}
+
+// The name and evaluation of the best skill in `skill_evals`
+fn best_skill(skill_evals: &[SkillEval]) -> Option<(String, EvalValue)> {
+    skill_evals
+        .iter()
+        .max_by(|a, b| a.partial_cmp(b).unwrap())
+        .map(|skill_eval| (skill_eval.line[0].name.clone(), skill_eval.eval.clone()))
+}
+
 #[derive(Clone)]
 pub struct AI {
     pub game: GameManager,
 
-    depth: u8,
+    settings: AISettings,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pool: Arc<rayon::ThreadPool>,
 }
 
 impl AI {
-    pub fn new(game: GameManager, depth: u8) -> Self {
-        AI { game, depth }
+    pub fn new(game: GameManager, settings: AISettings) -> Self {
+        AI {
+            game,
+
+            #[cfg(not(target_arch = "wasm32"))]
+            pool: Self::create_pool(settings.threads),
+
+            settings,
+        }
     }
 
     #[cfg(not(target_arch = "wasm32"))]
-    fn get_best_skill(&self) -> SkillEval {
-        let (tx, rx) = mpsc::channel();
+    fn create_pool(threads: usize) -> Arc<rayon::ThreadPool> {
+        Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("Failed to create the AI thread pool"),
+        )
+    }
 
-        let available_skills = self
-            .game
-            .player
-            .get_base_type()
-            .as_player()
-            .get_available_skills();
+    // The pool is only replaced if the number of threads changed
+    fn set_position(&mut self, game: GameManager, settings: AISettings) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if settings.threads != self.settings.threads {
+            self.pool = Self::create_pool(settings.threads);
+        }
 
-        // Spawn all threads
-        for skill in available_skills.clone() {
-            let current_tx = tx.clone();
-            let mut current_game = self.game.clone();
+        self.game = game;
+        self.settings = settings;
+    }
 
-            current_game
-                .player
-                .get_base_type_mut()
-                .as_player()
-                .set_current_skill(skill);
-            current_game.do_turn();
+    // The depth of the deepest search of a turn
+    fn max_depth(&self) -> u8 {
+        self.settings.depth.max(1)
+    }
 
-            let ai_thread = AIThread::new(current_game, self.depth - 1);
+    fn get_available_skills(&self) -> Vec<usize> {
+        ai_thread::get_available_skills(&self.game, &self.settings)
+    }
 
-            thread::spawn(move || current_tx.send(SkillEval::new(Some(skill), ai_thread.eval())));
-        }
+    // The settings to search the current position with.
+    // Deadlines that were already missed would make every line equally bad, so they're left out.
+    fn search_settings(&self) -> AISettings {
+        let player_ref = self.game.player.get_base_type();
+        let player = player_ref.as_player();
 
-        // Gather results
-        let mut result = SkillEval::lost();
-        for _ in 0..available_skills.len() {
-            let val = rx.recv().unwrap();
+        let mut settings = self.settings.clone();
+        settings
+            .constraints
+            .deadlines
+            .retain(|deadline| !deadline.is_missed(player, self.game.turn));
 
-            if val > result {
-                result = val;
-            }
+        settings
+    }
+
+    fn unmet_constraints(&self, plan: &SkillEval) -> Vec<String> {
+        let player_ref = self.game.player.get_base_type();
+        let player = player_ref.as_player();
+
+        let mut unmet =
+            self.settings
+                .constraints
+                .unmet(player, self.game.turn, player.get_available_skills());
+        if let EvalValue::MissedDeadline { skill, turn } = &plan.eval {
+            unmet.push(format!("{} can't be used before turn {}", skill, turn));
         }
 
-        result
+        unmet
     }
 
-    #[cfg(target_arch = "wasm32")]
-    fn get_best_skill(&self) -> SkillEval {
-        let available_skills = self
-            .game
-            .player
-            .get_base_type()
-            .as_player()
-            .get_available_skills();
+    // Evaluates every available skill to the given depth
+    #[cfg(not(target_arch = "wasm32"))]
+    fn get_skill_evals(
+        &self,
+        depth: u8,
+        context: &Arc<SearchContext>,
+        progress: &RootProgress,
+    ) -> Vec<SkillEval> {
+        let available_skills = self.get_available_skills();
 
-        let mut result = SkillEval::lost();
+        // `GameManager` isn't `Sync`, so every task gets its own copy of the game
+        let game = self.game.clone();
+        self.pool.install(move || {
+            available_skills
+                .into_par_iter()
+                .map_with(game, |game, skill| {
+                    let skill_eval =
+                        AIThread::new(game, depth, context.clone()).eval_first_skill(skill);
+                    progress.skills_done.fetch_add(1, Ordering::Relaxed);
+                    skill_eval
+                })
+                .collect()
+        })
+    }
 
-        for skill in available_skills.clone() {
-            let mut current_game = self.game.clone();
+    // Uses the best skill of a finished search
+    fn apply_search(&mut self, mut skill_evals: Vec<SkillEval>, stats: SearchStats) -> AITurn {
+        skill_evals.sort_by(|a, b| b.partial_cmp(a).unwrap());
 
-            current_game
-                .player
-                .get_base_type_mut()
-                .as_player()
-                .set_current_skill(skill);
-            current_game.do_turn();
+        // Move the recorded subtrees into a single tree, rather than copying them with the evaluations
+        let state = StateSummary::from(&self.game);
+        let children: Vec<SearchNode> = skill_evals
+            .iter_mut()
+            .filter_map(|skill_eval| skill_eval.tree.take())
+            .map(|tree| *tree)
+            .collect();
+        let tree = (!children.is_empty()).then(|| SearchNode {
+            skill: None,
+            state: state.clone(),
+            eval: skill_evals[0].eval.clone(),
+            children,
+        });
 
-            let ai_thread = AIThread::new(current_game, self.depth - 1);
+        let best_skill = skill_evals[0].clone();
+        let alternatives = skill_evals
+            .iter()
+            .skip(1)
+            .take(self.settings.alternatives)
+            .cloned()
+            .collect();
+        let unmet_constraints = self.unmet_constraints(&best_skill);
 
-            let val = SkillEval::new(Some(skill), ai_thread.eval());
+        let skill_name = self.use_skill(best_skill.index.unwrap());
 
-            if val > result {
-                result = val;
-            }
+        AITurn {
+            skill_name,
+            plan: best_skill,
+            alternatives,
+            skill_evals,
+            state,
+            tree,
+            stats,
+            unmet_constraints,
+            cached: false,
         }
-
-        result
     }
 
-    pub fn do_best_skill(&mut self, tx: mpsc::Sender<AICommunicationPacket>) {
-        let best_skill = self.get_best_skill();
-
+    fn use_skill(&mut self, skill: usize) -> String {
         let mut _player_ref = self.game.player.get_base_type_mut();
         let player = _player_ref.as_player();
-        let skills = player.skills.clone();
+        let skill_name = player.skills[skill].name.clone();
 
-        player.set_current_skill(best_skill.index.unwrap());
+        player.set_current_skill(skill);
         self.game.do_turn();
 
-        tx.send(AICommunicationPacket {
+        skill_name
+    }
+
+    // Uses the best skill of a turn that was searched before, in the same position
+    fn replay_turn(&mut self, mut turn: AITurn) -> AITurn {
+        self.use_skill(turn.plan.index.unwrap());
+        turn.cached = true;
+
+        turn
+    }
+
+    fn cache_key(&self) -> u64 {
+        RotationCache::key(&self.game.snapshot(), &self.settings)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn do_best_skill(
+        &mut self,
+        tx: mpsc::Sender<AICommunicationPacket>,
+        cancelled: Arc<AtomicBool>,
+    ) {
+        let context = Arc::new(SearchContext {
+            cancelled,
+            ..SearchContext::new(self.search_settings(), &self.game)
+        });
+        let progress = Arc::new(RootProgress::new(self.get_available_skills().len()));
+        let start_time = Instant::now();
+
+        // Report the progress periodically until the search is done
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let reporter = {
+            let context = context.clone();
+            let progress = progress.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                while let Err(mpsc::RecvTimeoutError::Timeout) =
+                    done_rx.recv_timeout(PROGRESS_INTERVAL)
+                {
+                    let report = progress.report(&context, start_time);
+                    if tx.send(AICommunicationPacket::Progress(report)).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+
+        // Search one depth at a time, so there's always a best skill to report.
+        // The shallower searches are a small fraction of the deepest one.
+        // Every depth searches the tree again from the root, so nodes are counted per depth.
+        let mut skill_evals = Vec::new();
+        let mut depth_start_time = start_time;
+        for depth in 1..=self.max_depth() {
+            progress.start_depth(depth);
+            context.nodes.store(0, Ordering::Relaxed);
+            context.recorded_nodes.store(0, Ordering::Relaxed);
+            depth_start_time = Instant::now();
+            skill_evals = self.get_skill_evals(depth, &context, &progress);
+            if context.is_cancelled() {
+                break;
+            }
+            *progress.best_skill.lock().unwrap() = best_skill(&skill_evals);
+        }
+
+        drop(done_tx);
+        reporter.join().unwrap();
+
+        // The evaluations of a cancelled search are incomplete
+        if context.is_cancelled() {
+            return;
+        }
+
+        let stats = SearchStats {
+            nodes: context.nodes.load(Ordering::Relaxed),
+            elapsed: depth_start_time.elapsed(),
+        };
+        let turn = self.apply_search(skill_evals, stats);
+
+        // The receiver is gone if the search was abandoned
+        tx.send(AICommunicationPacket::Turn {
             new_game: self.game.clone(),
-            skill_name: skills[best_skill.index.unwrap()].name.clone(),
+            turn: Box::new(turn),
         })
-        .unwrap();
+        .ok();
     }
 }
@@ -1,15 +1,153 @@
+use crate::ai::types::ai_settings::AISettings;
 use crate::ai::types::eval_value::EvalValue;
-use crate::game::game_manager::GameManager;
+use crate::ai::types::objective::Objective;
+use crate::ai::types::rotation_constraints::SkillDeadline;
+use crate::ai::types::search_tree::SearchNode;
+use crate::ai::types::skill_eval::{PlannedSkill, SkillEval};
+use crate::ai::types::state_summary::StateSummary;
+use crate::game::game_manager::{GameManager, GameStatus};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 
-pub struct AIThread {
-    game: GameManager,
+// Nodes with at least this depth left split their children between the threads of the pool.
+// Shallower nodes are searched on a single thread, since splitting them costs more than it saves.
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_PARALLEL_DEPTH: u8 = 2;
+
+// State shared by all the threads of a single search
+pub struct SearchContext {
+    pub settings: AISettings,
+
+    // Number of nodes searched so far
+    pub nodes: AtomicU64,
+
+    // Number of nodes recorded into the search tree so far
+    pub recorded_nodes: AtomicUsize,
+
+    // Set when the result is no longer needed, the search then returns as soon as possible
+    pub cancelled: Arc<AtomicBool>,
+
+    // Only measured with `Objective::TurnsToKill`, which projects the rest of the fight with it
+    pub fallback: FallbackRate,
+}
+
+impl SearchContext {
+    pub fn new(settings: AISettings, game: &GameManager) -> Self {
+        Self {
+            fallback: match settings.objective {
+                Objective::TurnsToKill => FallbackRate::measure(game),
+                _ => FallbackRate::default(),
+            },
+            settings,
+            nodes: AtomicU64::new(0),
+            recorded_nodes: AtomicUsize::new(0),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Whether the next node should be recorded into the search tree
+    fn record_node(&self) -> bool {
+        self.settings.record_nodes > 0
+            && self.recorded_nodes.fetch_add(1, Ordering::Relaxed) < self.settings.record_nodes
+    }
+}
+
+// Turns of the free skill that are played to measure the `FallbackRate`
+const FALLBACK_TURNS: i32 = 8;
+
+// The damage the player deals and the mana they regain per turn by only using their free skill,
+// which costs no mana and has no cooldown. That's what a rotation falls back to without mana.
+#[derive(Default)]
+pub struct FallbackRate {
+    pub damage_per_turn: f64,
+    pub mana_per_turn: f64,
+}
+
+impl FallbackRate {
+    // Plays the free skill from `game` for a few turns, since its damage depends on the fight
+    fn measure(game: &GameManager) -> Self {
+        let free_skill = game
+            .player
+            .get_base_type()
+            .as_player()
+            .skills
+            .iter()
+            .position(|skill| skill.mana == 0 && skill.cooldown == 0);
+        let Some(skill) = free_skill else {
+            return Self::default();
+        };
+
+        let mut game = game.clone();
+        let start_hp = enemies_hp(&game);
+        let start_mp = game.player.get_base_entity().mp;
+
+        let mut turns = 0;
+        while turns < FALLBACK_TURNS && matches!(game.get_status(), GameStatus::InProgress) {
+            game.player
+                .get_base_type_mut()
+                .as_player()
+                .set_current_skill(skill);
+            game.do_turn();
+            turns += 1;
+        }
+
+        if turns == 0 {
+            return Self::default();
+        }
+
+        Self {
+            damage_per_turn: (start_hp - enemies_hp(&game)) / turns as f64,
+            mana_per_turn: (game.player.get_base_entity().mp - start_mp) as f64 / turns as f64,
+        }
+    }
+}
+
+fn enemies_hp(game: &GameManager) -> f64 {
+    game.enemies
+        .iter()
+        .map(|enemy| enemy.get_base_entity().hp.max(0) as f64)
+        .sum()
+}
+
+// The skills the player may use in `game`.
+// Constraints that can't be met are ignored, rather than leaving the player without a skill.
+pub fn get_available_skills(game: &GameManager, settings: &AISettings) -> Vec<usize> {
+    let player_ref = game.player.get_base_type();
+    let player = player_ref.as_player();
+
+    let skills = player.get_available_skills();
+    let allowed_skills = settings
+        .constraints
+        .filter(player, game.turn, skills.clone());
+
+    if allowed_skills.is_empty() {
+        skills
+    } else {
+        allowed_skills
+    }
+}
+
+// Searches the positions that follow `game`, which is only cloned to play the skills
+pub struct AIThread<'a> {
+    game: &'a GameManager,
 
     depth: u8,
+    context: Arc<SearchContext>,
 }
 
-impl AIThread {
-    pub fn new(game: GameManager, depth: u8) -> Self {
-        AIThread { game, depth }
+impl<'a> AIThread<'a> {
+    pub fn new(game: &'a GameManager, depth: u8, context: Arc<SearchContext>) -> Self {
+        AIThread {
+            game,
+            depth,
+            context,
+        }
     }
 
     fn eval_without_depth(&self) -> EvalValue {
@@ -20,54 +158,545 @@ impl AIThread {
             return EvalValue::Lost;
         }
 
+        let player_hp_fraction = player_hp / player_base_entity.max_hp as f64;
+        let resources = player_hp_fraction
+            + player_base_entity.mp.max(0) as f64 / player_base_entity.max_mp as f64;
+
         let mut enemies_hp = 0.0;
         let mut enemies_max_hp = 0.0;
         for enemy in &self.game.enemies {
             let enemy_base_entity = enemy.get_base_entity();
-            enemies_hp += enemy_base_entity.hp as f64;
+            enemies_hp += enemy_base_entity.hp.max(0) as f64;
             enemies_max_hp += enemy_base_entity.max_hp as f64;
         }
 
+        // The turn counter is incremented at the start of `GameManager::do_turn`
+        let turns_played = self.game.turn - 1;
+
         if enemies_hp <= 0.0 {
-            return EvalValue::Won;
+            return EvalValue::Won {
+                turn: turns_played,
+                resources,
+            };
+        }
+
+        if let Some(deadline) = self.missed_deadline() {
+            return EvalValue::MissedDeadline {
+                skill: deadline.skill.clone(),
+                turn: deadline.turn,
+            };
         }
 
-        EvalValue::InProgress(-100.0 * enemies_hp / enemies_max_hp)
+        let score = match self.context.settings.objective {
+            Objective::EnemyHealth => -100.0 * enemies_hp / enemies_max_hp,
+            Objective::TurnsToKill => {
+                // Fights start at full MP, like they start with the enemies at full HP
+                let mana_spent = (player_base_entity.max_mp - player_base_entity.mp) as f64;
+                let turns_to_kill = turns_played as f64
+                    + Self::remaining_turns(
+                        enemies_hp,
+                        (enemies_max_hp - enemies_hp) / turns_played.max(1) as f64,
+                        mana_spent / turns_played.max(1) as f64,
+                        player_base_entity.mp.max(0) as f64,
+                        &self.context.fallback,
+                    );
+
+                -turns_to_kill
+            }
+            Objective::WinProbability => {
+                // A heuristic, only the rollouts sampled in `eval_first_skill` measure how often a skill wins
+                player_hp_fraction / (player_hp_fraction + enemies_hp / enemies_max_hp)
+            }
+        };
+
+        EvalValue::InProgress { score, resources }
     }
 
-    pub fn eval(&self) -> EvalValue {
-        if self.depth == 0 {
-            return self.eval_without_depth();
+    // Projects how many more turns it takes to deal `enemies_hp` damage. The line's damage per turn
+    // lasts while the player's `mp` pays for its mana per turn. After that, its skills are mixed with
+    // the free skill, so the mana the free skill regains pays for them.
+    // Lines that spend their mana on a burst then take longer to finish long fights.
+    fn remaining_turns(
+        enemies_hp: f64,
+        damage_per_turn: f64,
+        mana_per_turn: f64,
+        mp: f64,
+        fallback: &FallbackRate,
+    ) -> f64 {
+        if damage_per_turn <= fallback.damage_per_turn {
+            return enemies_hp / fallback.damage_per_turn;
         }
 
-        let mut best_skill = EvalValue::Lost;
+        let burst_turns = if mana_per_turn > 0.0 {
+            mp / mana_per_turn
+        } else {
+            f64::INFINITY
+        };
+        if enemies_hp <= damage_per_turn * burst_turns {
+            return enemies_hp / damage_per_turn;
+        }
+
+        // The share of the turns that use the line's skills, so that the mana stays the same
+        let mana_regained = fallback.mana_per_turn.max(0.0);
+        let line_share = mana_regained / (mana_per_turn + mana_regained);
+        let sustained_damage_per_turn =
+            line_share * damage_per_turn + (1.0 - line_share) * fallback.damage_per_turn;
+
+        burst_turns + (enemies_hp - damage_per_turn * burst_turns) / sustained_damage_per_turn
+    }
+
+    // The first deadline of the constraints that the line leading to this position missed
+    fn missed_deadline(&self) -> Option<&SkillDeadline> {
+        let player_ref = self.game.player.get_base_type();
+        self.context
+            .settings
+            .constraints
+            .missed_deadline(player_ref.as_player(), self.game.turn)
+    }
+
+    // Combines the evaluations of several rollouts of the same skill
+    fn combine_samples(evals: &[EvalValue]) -> EvalValue {
+        // Missing a deadline is still better than losing
+        if evals
+            .iter()
+            .all(|eval| matches!(eval, EvalValue::Lost | EvalValue::MissedDeadline { .. }))
+        {
+            return evals
+                .iter()
+                .max_by(|a, b| a.partial_cmp(b).unwrap())
+                .unwrap()
+                .clone();
+        }
 
-        for skill in self
-            .game
+        let count = evals.len() as f64;
+        let mut won = 0.0;
+        let mut unfinished = 0.0;
+        let mut estimate = 0.0;
+        let mut resources = 0.0;
+        let mut last_turn = Some(0);
+        for eval in evals {
+            match eval {
+                EvalValue::Won {
+                    turn,
+                    resources: eval_resources,
+                } => {
+                    won += 1.0;
+                    resources += eval_resources;
+                    last_turn = last_turn.map(|last_turn: i32| last_turn.max(*turn));
+                }
+                EvalValue::InProgress {
+                    score,
+                    resources: eval_resources,
+                } => {
+                    unfinished += 1.0;
+                    estimate += score;
+                    resources += eval_resources;
+                    last_turn = None;
+                }
+                // Only the root is sampled, so its rollouts aren't combined evaluations
+                EvalValue::Sampled { .. } => unreachable!(),
+                EvalValue::Lost | EvalValue::MissedDeadline { .. } => last_turn = None,
+            }
+        }
+        resources /= count;
+
+        if let Some(turn) = last_turn {
+            // Every rollout won
+            return EvalValue::Won { turn, resources };
+        }
+
+        EvalValue::Sampled {
+            won: won / count,
+            unfinished: unfinished / count,
+            estimate: if unfinished > 0.0 {
+                estimate / unfinished
+            } else {
+                0.0
+            },
+            resources,
+        }
+    }
+
+    // Evaluates using `skill` in the current position, with a single random outcome of every turn.
+    // Returns the evaluation and the game after the skill.
+    fn eval_skill(&self, skill: usize) -> (SkillEval, GameManager) {
+        let mut current_game = self.game.clone();
+        current_game
             .player
-            .get_base_type()
+            .get_base_type_mut()
             .as_player()
-            .get_available_skills()
-        {
-            let mut current_game = self.game.clone();
-            let mut _player_ref = current_game.player.get_base_type_mut();
-            let player = _player_ref.as_player();
+            .set_current_skill(skill);
+        current_game.do_turn();
 
-            player.set_current_skill(skill);
-            current_game.do_turn();
+        let mut skill_eval =
+            AIThread::new(&current_game, self.depth - 1, self.context.clone()).eval();
+        if let Some(tree) = &mut skill_eval.tree {
+            tree.skill = Some(self.skill_name(skill));
+        }
+
+        (skill_eval, current_game)
+    }
 
-            let ai_thread = AIThread::new(current_game, self.depth - 1);
-            let skill_eval = ai_thread.eval();
+    fn skill_name(&self, skill: usize) -> String {
+        self.game.player.get_base_type().as_player().skills[skill]
+            .name
+            .clone()
+    }
+
+    fn planned_skill(&self, skill: usize, game: &GameManager, eval: &EvalValue) -> PlannedSkill {
+        PlannedSkill {
+            index: skill,
+            name: self.skill_name(skill),
+            state: StateSummary::from(game),
+            eval: eval.clone(),
+        }
+    }
 
-            if skill_eval == EvalValue::Won {
-                return skill_eval;
+    fn search_node(&self, eval: &EvalValue, children: Vec<SearchNode>) -> Box<SearchNode> {
+        Box::new(SearchNode {
+            skill: None,
+            state: StateSummary::from(self.game),
+            eval: eval.clone(),
+            children,
+        })
+    }
+
+    // Plays `skill` and then `line` from the current position, with fresh random outcomes.
+    // The search keeps the best of the outcomes it rolled for every skill, so its evaluation of the
+    // line it picked is optimistic. The outcome of the replay isn't, since it didn't pick the line.
+    fn replay_line(&self, skill: usize, line: &[PlannedSkill]) -> EvalValue {
+        let mut game = self.game.clone();
+        let skills =
+            std::iter::once(skill).chain(line.iter().map(|planned_skill| planned_skill.index));
+        for skill in skills {
+            if !matches!(game.get_status(), GameStatus::InProgress) {
+                break;
             }
 
+            {
+                let mut player_ref = game.player.get_base_type_mut();
+                let player = player_ref.as_player();
+                // Other outcomes can leave the player without the MP for the rest of the line
+                if !player.get_available_skills().contains(&skill) {
+                    break;
+                }
+                player.set_current_skill(skill);
+            }
+            game.do_turn();
+        }
+
+        AIThread::new(&game, 0, self.context.clone()).eval_without_depth()
+    }
+
+    // Evaluates using `skill` in the current position, including it in the line.
+    // With `Objective::WinProbability`, several rollouts of the whole search are sampled from here,
+    // so the sampling cost doesn't multiply with the depth. Every rollout is scored by replaying the
+    // line it found, and the line is taken from the median rollout.
+    pub fn eval_first_skill(&self, skill: usize) -> SkillEval {
+        let (samples, replay) = match self.context.settings.objective {
+            Objective::WinProbability => (self.context.settings.samples.max(1), true),
+            _ => (1, false),
+        };
+
+        let mut rollouts: Vec<(SkillEval, GameManager)> = (0..samples)
+            .map(|_| {
+                let (mut skill_eval, game) = self.eval_skill(skill);
+                if replay {
+                    skill_eval.eval = self.replay_line(skill, &skill_eval.line);
+                }
+
+                (skill_eval, game)
+            })
+            .collect();
+        rollouts.sort_by(|a, b| {
+            a.0.eval
+                .partial_cmp(&b.0.eval)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let evals: Vec<EvalValue> = rollouts
+            .iter()
+            .map(|(skill_eval, _)| skill_eval.eval.clone())
+            .collect();
+
+        let (mut skill_eval, game) = rollouts.swap_remove(samples / 2);
+        if samples > 1 {
+            skill_eval.eval = Self::combine_samples(&evals);
+        }
+
+        let planned_skill = self.planned_skill(skill, &game, &skill_eval.eval);
+        skill_eval.with_first_skill(planned_skill)
+    }
+
+    // Evaluates every skill in `skills`, splitting the work between the threads of the pool if needed
+    fn eval_skills(&self, skills: Vec<usize>) -> Vec<(SkillEval, GameManager)> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.depth >= MIN_PARALLEL_DEPTH {
+            // `GameManager` isn't `Sync`, so every task gets its own copy of the game
+            let depth = self.depth;
+            let context = &self.context;
+            return skills
+                .into_par_iter()
+                .map_with(self.game.clone(), |game, skill| {
+                    AIThread::new(game, depth, context.clone()).eval_skill(skill)
+                })
+                .collect();
+        }
+
+        skills
+            .into_iter()
+            .map(|skill| self.eval_skill(skill))
+            .collect()
+    }
+
+    pub fn eval(&self) -> SkillEval {
+        self.context.nodes.fetch_add(1, Ordering::Relaxed);
+        if self.context.is_cancelled() {
+            return SkillEval::lost();
+        }
+        let record = self.context.record_node();
+
+        // Lines that missed a deadline aren't searched further
+        if self.depth == 0
+            || !matches!(self.game.get_status(), GameStatus::InProgress)
+            || self.missed_deadline().is_some()
+        {
+            let mut result = SkillEval::new(None, self.eval_without_depth());
+            if record {
+                result.tree = Some(self.search_node(&result.eval, Vec::new()));
+            }
+
+            return result;
+        }
+
+        let mut best_skill = SkillEval::lost();
+        let mut children = Vec::new();
+
+        let skills = get_available_skills(self.game, &self.context.settings);
+
+        for (skill, (mut skill_eval, game)) in
+            skills.clone().into_iter().zip(self.eval_skills(skills))
+        {
+            children.extend(skill_eval.tree.take().map(|tree| *tree));
+
             if skill_eval > best_skill {
-                best_skill = skill_eval
+                // Only summarize the state when the line is kept, since it's relatively expensive
+                let planned_skill = self.planned_skill(skill, &game, &skill_eval.eval);
+                best_skill = skill_eval.with_first_skill(planned_skill);
+
+                // No other line can win sooner than on this turn
+                if matches!(best_skill.eval, EvalValue::Won { turn, .. } if turn == self.game.turn)
+                {
+                    break;
+                }
             }
         }
 
+        if record {
+            best_skill.tree = Some(self.search_node(&best_skill.eval, children));
+        }
+
         best_skill
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::types::rotation_constraints::{RotationConstraints, SkillDeadline};
+    use crate::game::entities::armors::Armor;
+    use crate::game::entities::enemies::Challenge;
+    use crate::game::types::damage::DamageRange;
+    use crate::game::types::dict::Dict;
+
+    #[test]
+    fn combine_samples_reports_the_share_of_won_rollouts() {
+        let won = EvalValue::Won {
+            turn: 5,
+            resources: 1.0,
+        };
+        let unfinished = EvalValue::InProgress {
+            score: 0.5,
+            resources: 1.0,
+        };
+
+        let combined =
+            AIThread::combine_samples(&[won.clone(), won.clone(), unfinished, EvalValue::Lost]);
+        assert_eq!(
+            combined,
+            EvalValue::Sampled {
+                won: 0.5,
+                unfinished: 0.25,
+                estimate: 0.5,
+                resources: 0.75,
+            }
+        );
+        assert_eq!(combined.score(), Some(0.625));
+
+        assert_eq!(AIThread::combine_samples(&[won.clone(), won.clone()]), won);
+        assert_eq!(
+            AIThread::combine_samples(&[EvalValue::Lost, EvalValue::Lost]),
+            EvalValue::Lost
+        );
+    }
+
+    fn settings(depth: u8, objective: Objective, constraints: RotationConstraints) -> AISettings {
+        AISettings {
+            depth,
+            alternatives: 0,
+            objective,
+            samples: 1,
+            threads: 1,
+            record_nodes: 0,
+            constraints,
+        }
+    }
+
+    fn game() -> GameManager {
+        GameManager::new(
+            Armor::Pirate.create(Dict::new(), 90),
+            Challenge::Dummy.create(90),
+        )
+    }
+
+    fn skill_index(game: &GameManager, name: &str) -> usize {
+        game.player
+            .get_base_type()
+            .as_player()
+            .skills
+            .iter()
+            .position(|skill| skill.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn lines_that_miss_a_deadline_rank_below_the_others() {
+        // Spending more than 10 MP on turn 1 leaves too little for Curse, the deadline then can't be
+        // forced on turn 2 anymore
+        let mut game = game();
+        game.player.get_base_entity_mut().mp = 50;
+
+        let constraints = RotationConstraints {
+            deadlines: vec![SkillDeadline {
+                skill: "Curse".to_string(),
+                turn: 3,
+            }],
+            ..Default::default()
+        };
+        let context = Arc::new(SearchContext::new(
+            settings(2, Objective::EnemyHealth, constraints),
+            &game,
+        ));
+
+        let skill_eval = AIThread::new(&game, 2, context.clone()).eval();
+        assert!(skill_eval.line.iter().any(|skill| skill.name == "Curse"));
+        assert!(!matches!(skill_eval.eval, EvalValue::MissedDeadline { .. }));
+
+        for _ in 0..2 {
+            let attack = skill_index(&game, "Attack");
+            game.player
+                .get_base_type_mut()
+                .as_player()
+                .set_current_skill(attack);
+            game.do_turn();
+        }
+        assert_eq!(
+            AIThread::new(&game, 2, context).eval().eval,
+            EvalValue::MissedDeadline {
+                skill: "Curse".to_string(),
+                turn: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn cancelled_search_stops_at_the_root() {
+        let game = game();
+        let context = Arc::new(SearchContext::new(
+            settings(3, Objective::EnemyHealth, RotationConstraints::default()),
+            &game,
+        ));
+        context.cancelled.store(true, Ordering::Relaxed);
+
+        let skill_eval = AIThread::new(&game, 3, context.clone()).eval();
+        assert_eq!(skill_eval.eval, EvalValue::Lost);
+        assert_eq!(context.nodes.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn turns_to_kill_saves_mana_for_a_long_fight() {
+        let mut game = game();
+        let cannon = skill_index(&game, "Cannon");
+        game.player
+            .get_base_type_mut()
+            .as_player()
+            .set_current_skill(cannon);
+        game.do_turn();
+
+        let best_skill = |objective| {
+            let context = Arc::new(SearchContext::new(
+                settings(1, objective, RotationConstraints::default()),
+                &game,
+            ));
+            let skill_eval = AIThread::new(&game, 1, context).eval();
+            skill_eval.line[0].name.clone()
+        };
+
+        // Curse deals the most damage this turn, but the Dummy takes far longer to kill than the
+        // player's MP lasts, and Attack regains MP
+        assert_eq!(best_skill(Objective::EnemyHealth), "Curse");
+        assert_eq!(best_skill(Objective::TurnsToKill), "Attack");
+    }
+
+    #[test]
+    fn win_probability_isnt_biased_by_the_search() {
+        // Whether Attack and one more skill kill the Dummy is down to the damage rolls
+        let mut game = game();
+        game.player.get_base_entity_mut().dmg = DamageRange::new(0.0, 100.0);
+        game.enemies[0].get_base_entity_mut().hp = 250;
+
+        let samples = 400;
+        let context = Arc::new(SearchContext::new(
+            AISettings {
+                samples,
+                ..settings(2, Objective::WinProbability, RotationConstraints::default())
+            },
+            &game,
+        ));
+        let ai_thread = AIThread::new(&game, 2, context);
+        let attack = skill_index(&game, "Attack");
+
+        let win_rate = |second_skill: usize| {
+            let line = [PlannedSkill {
+                index: second_skill,
+                name: String::new(),
+                state: StateSummary::from(&game),
+                eval: EvalValue::Lost,
+            }];
+            let wins = (0..samples)
+                .filter(|_| matches!(ai_thread.replay_line(attack, &line), EvalValue::Won { .. }))
+                .count();
+
+            wins as f64 / samples as f64
+        };
+        let best_win_rate = game
+            .player
+            .get_base_type()
+            .as_player()
+            .get_available_skills()
+            .into_iter()
+            .map(win_rate)
+            .fold(0.0, f64::max);
+
+        // Every rollout of the search has a dozen second skills to roll a kill with, so scoring
+        // rollouts by the search's own outcomes wins more often than any line really does
+        let EvalValue::Sampled { won, .. } = ai_thread.eval_first_skill(attack).eval else {
+            panic!("The rollouts should be sampled");
+        };
+        assert!(
+            won <= best_win_rate + 0.1,
+            "{} of the rollouts won, but the best line only wins {}",
+            won,
+            best_win_rate
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// Web Workers for the web build, where threads aren't available.
+// The workers load this same binary, and evaluate one root skill per request.
+
+use crate::ai::ai_thread::{AIThread, SearchContext};
+use crate::ai::types::ai_settings::AISettings;
+use crate::ai::types::skill_eval::SkillEval;
+use crate::game::game_manager::{GameManager, GameSnapshot};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    Blob, BlobPropertyBag, DedicatedWorkerGlobalScope, MessageEvent, Url, Worker, WorkerOptions,
+    WorkerType,
+};
+
+// Sent by a worker once it can receive requests
+const READY_MESSAGE: &str = "ready";
+
+#[wasm_bindgen]
+extern "C" {
+    // The URL of the JS module that loads this binary
+    #[wasm_bindgen(js_namespace = ["import", "meta"], js_name = url)]
+    static SCRIPT_URL: JsValue;
+}
+
+#[derive(Serialize, Deserialize)]
+struct WorkerRequest {
+    // Responses of cancelled searches are ignored
+    search_id: u32,
+    game: GameSnapshot,
+    settings: AISettings,
+    skill: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkerResponse {
+    search_id: u32,
+    pub skill_eval: SkillEval,
+    pub nodes: u64,
+}
+
+pub fn is_worker() -> bool {
+    js_sys::global()
+        .dyn_ref::<DedicatedWorkerGlobalScope>()
+        .is_some()
+}
+
+// Runs inside the worker, handling search requests until the worker is terminated
+pub fn start_worker() {
+    console_error_panic_hook::set_once();
+
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let response_scope = scope.clone();
+
+    // Errors are thrown, so the pool gets them through the worker's `onerror`
+    let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+        let request: WorkerRequest = match event
+            .data()
+            .as_string()
+            .map(|data| serde_json::from_str(&data))
+        {
+            Some(Ok(request)) => request,
+            Some(Err(error)) => {
+                wasm_bindgen::throw_str(&format!("Invalid AI worker request: {}", error))
+            }
+            None => wasm_bindgen::throw_str("AI worker requests must be text"),
+        };
+
+        let game = GameManager::from(request.game);
+        let context = Arc::new(SearchContext::new(request.settings, &game));
+        let ai_thread = AIThread::new(&game, context.settings.depth, context.clone());
+        let response = WorkerResponse {
+            search_id: request.search_id,
+            skill_eval: ai_thread.eval_first_skill(request.skill),
+            nodes: context.nodes.load(Ordering::Relaxed),
+        };
+
+        let response = match serde_json::to_string(&response) {
+            Ok(response) => response,
+            Err(error) => {
+                wasm_bindgen::throw_str(&format!("Invalid AI worker response: {}", error))
+            }
+        };
+        if let Err(error) = response_scope.post_message(&JsValue::from_str(&response)) {
+            wasm_bindgen::throw_val(error);
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+
+    if let Err(error) = scope.post_message(&JsValue::from_str(READY_MESSAGE)) {
+        wasm_bindgen::throw_val(error);
+    }
+}
+
+// A readable message for an error thrown by the browser
+fn error_message(error: &JsValue) -> String {
+    error
+        .as_string()
+        .or_else(|| {
+            js_sys::Reflect::get(error, &JsValue::from_str("message"))
+                .ok()
+                .and_then(|message| message.as_string())
+        })
+        .unwrap_or_else(|| format!("{:?}", error))
+}
+
+#[derive(Default)]
+struct PoolState {
+    // Serialized requests waiting for a free worker
+    queue: VecDeque<String>,
+    idle_workers: Vec<Worker>,
+    responses: Vec<WorkerResponse>,
+
+    // The search whose responses are kept
+    search_id: u32,
+
+    // Set once a worker failed, the pool can't be trusted to answer every request after that
+    error: Option<String>,
+}
+
+impl PoolState {
+    // Keeps the first error, later ones are usually caused by it
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+
+    // Hands `request` to `worker`, or fails the pool if it can't be sent
+    fn send(&mut self, worker: &Worker, request: &str) {
+        if let Err(error) = worker.post_message(&JsValue::from_str(request)) {
+            self.fail(format!(
+                "Couldn't send a request to an AI worker: {}",
+                error_message(&error)
+            ));
+        }
+    }
+}
+
+struct AIWorker {
+    worker: Worker,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(JsValue)>,
+}
+
+pub struct AIWorkerPool {
+    workers: Vec<AIWorker>,
+    script_url: String,
+    state: Rc<RefCell<PoolState>>,
+}
+
+impl AIWorkerPool {
+    // Fails if the browser doesn't allow the workers, e.g. when its CSP blocks blob URLs
+    pub fn new(size: usize) -> Result<Self, String> {
+        // Every worker imports the module that loaded this binary, which runs `main` again
+        let module_url = SCRIPT_URL
+            .as_string()
+            .ok_or("The URL of the app's module is unknown")?;
+        let script = format!("import init from \"{}\";\nawait init();\n", module_url);
+        let blob = Blob::new_with_str_sequence_and_options(
+            &js_sys::Array::of1(&JsValue::from_str(&script)),
+            BlobPropertyBag::new().type_("application/javascript"),
+        )
+        .map_err(|error| error_message(&error))?;
+        let script_url =
+            Url::create_object_url_with_blob(&blob).map_err(|error| error_message(&error))?;
+
+        // Created before the workers, so the ones that were created are terminated if another one fails
+        let mut pool = Self {
+            workers: Vec::new(),
+            script_url,
+            state: Rc::new(RefCell::new(PoolState::default())),
+        };
+
+        for _ in 0..size.max(1) {
+            let worker = Worker::new_with_options(
+                &pool.script_url,
+                WorkerOptions::new().type_(WorkerType::Module),
+            )
+            .map_err(|error| format!("Couldn't create an AI worker: {}", error_message(&error)))?;
+
+            let worker_state = pool.state.clone();
+            let message_worker = worker.clone();
+            let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+                let mut state = worker_state.borrow_mut();
+
+                match event.data().as_string() {
+                    Some(data) if data == READY_MESSAGE => {}
+                    Some(data) => match serde_json::from_str::<WorkerResponse>(&data) {
+                        Ok(response) if response.search_id == state.search_id => {
+                            state.responses.push(response)
+                        }
+                        Ok(_) => {}
+                        Err(error) => state.fail(format!("Invalid AI worker response: {}", error)),
+                    },
+                    None => state.fail("AI worker responses must be text".to_string()),
+                }
+
+                // The worker is free, give it the next request
+                if let Some(request) = state.queue.pop_front() {
+                    state.send(&message_worker, &request);
+                } else {
+                    state.idle_workers.push(message_worker.clone());
+                }
+            }) as Box<dyn FnMut(MessageEvent)>);
+            worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+            // Called if the worker's module can't be loaded, or if it throws or panics
+            let worker_state = pool.state.clone();
+            let on_error = Closure::wrap(Box::new(move |event: JsValue| {
+                worker_state
+                    .borrow_mut()
+                    .fail(format!("An AI worker failed: {}", error_message(&event)));
+            }) as Box<dyn FnMut(JsValue)>);
+            worker.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+            pool.workers.push(AIWorker {
+                worker,
+                _on_message: on_message,
+                _on_error: on_error,
+            });
+        }
+
+        Ok(pool)
+    }
+
+    // Requests the evaluation of every skill in `skills`
+    // Requests of an earlier search that weren't handled yet are dropped
+    pub fn search(&self, game: &GameManager, settings: &AISettings, skills: Vec<usize>) {
+        self.cancel();
+
+        let game = game.snapshot();
+        let mut state = self.state.borrow_mut();
+
+        for skill in skills {
+            let request = match serde_json::to_string(&WorkerRequest {
+                search_id: state.search_id,
+                game: game.clone(),
+                settings: settings.clone(),
+                skill,
+            }) {
+                Ok(request) => request,
+                Err(error) => return state.fail(format!("Invalid AI worker request: {}", error)),
+            };
+
+            if let Some(worker) = state.idle_workers.pop() {
+                state.send(&worker, &request);
+            } else {
+                state.queue.push_back(request);
+            }
+        }
+    }
+
+    // Drops the waiting requests, and ignores the responses to the ones already being handled
+    pub fn cancel(&self) {
+        let mut state = self.state.borrow_mut();
+        state.search_id = state.search_id.wrapping_add(1);
+        state.queue.clear();
+        state.responses.clear();
+    }
+
+    pub fn take_responses(&self) -> Vec<WorkerResponse> {
+        std::mem::take(&mut self.state.borrow_mut().responses)
+    }
+
+    // Why a worker failed, if one did
+    pub fn error(&self) -> Option<String> {
+        self.state.borrow().error.clone()
+    }
+}
+
+impl Drop for AIWorkerPool {
+    fn drop(&mut self) {
+        for worker in &self.workers {
+            worker.worker.terminate();
+        }
+
+        Url::revoke_object_url(&self.script_url).ok();
+    }
+}
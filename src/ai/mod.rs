@@ -1,3 +1,6 @@
 pub mod ai_communication;
 mod ai_thread;
+#[cfg(target_arch = "wasm32")]
+pub mod ai_worker;
+pub mod rotation_cache;
 pub mod types;
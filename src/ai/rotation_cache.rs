@@ -0,0 +1,140 @@
+use crate::ai::ai_communication::AITurn;
+use crate::ai::types::ai_settings::AISettings;
+use crate::game::game_manager::GameSnapshot;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+// The oldest turns are removed once the cache is full, to keep the storage small
+const MAX_TURNS: usize = 64;
+
+// Changing the search in a way that changes its results should bump this, to ignore old results
+const CACHE_VERSION: u32 = 6;
+
+#[cfg(not(target_arch = "wasm32"))]
+const FILE_NAME: &str = "rotation_cache.json";
+
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "rotation_cache";
+
+pub type SharedRotationCache = Rc<RefCell<RotationCache>>;
+
+// The AI's turns in positions it already solved, saved between sessions
+#[derive(Serialize, Deserialize, Default)]
+pub struct RotationCache {
+    turns: HashMap<u64, AITurn>,
+
+    // Keys from the oldest to the newest
+    order: VecDeque<u64>,
+
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl RotationCache {
+    // Identifies a position and the settings it's searched with.
+    // The game holds everything `GameOptions` creates it from, so it's hashed instead.
+    pub fn key(game: &GameSnapshot, settings: &AISettings) -> u64 {
+        // These settings don't change the chosen skill
+        let mut settings = settings.clone();
+        settings.threads = 0;
+        settings.record_nodes = 0;
+
+        // `serde_json::Value` sorts map keys, so equal games are always serialized the same way
+        let value = serde_json::to_value((CACHE_VERSION, game, settings)).unwrap();
+
+        fnv1a(value.to_string().as_bytes())
+    }
+
+    pub fn get(&self, key: u64) -> Option<AITurn> {
+        self.turns.get(&key).cloned()
+    }
+
+    pub fn insert(&mut self, key: u64, turn: &AITurn) {
+        let mut turn = turn.clone();
+        turn.tree = None;
+
+        if self.turns.insert(key, turn).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_TURNS {
+            let oldest_key = self.order.pop_front().unwrap();
+            self.turns.remove(&oldest_key);
+        }
+
+        self.changed = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+        self.order.clear();
+        self.changed = true;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> Option<std::path::PathBuf> {
+        directories_next::ProjectDirs::from("", "", "InnSolver")
+            .map(|dirs| dirs.data_dir().join(FILE_NAME))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    // The cache is only an optimization, so failing to save it is ignored
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+
+        if let Some(path) = Self::path() {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(path, serde_json::to_string(self).unwrap());
+        }
+        self.changed = false;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if !self.changed {
+            return;
+        }
+
+        eframe::set_value(storage, STORAGE_KEY, self);
+        self.changed = false;
+    }
+}
+
+// 64-bit FNV-1a, which is stable between runs and platforms unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
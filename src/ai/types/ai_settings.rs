@@ -0,0 +1,24 @@
+use crate::ai::types::objective::Objective;
+use crate::ai::types::rotation_constraints::RotationConstraints;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AISettings {
+    pub depth: u8,
+
+    // Number of alternative first skills to report
+    pub alternatives: usize,
+
+    pub objective: Objective,
+
+    // Number of rollouts sampled for every first skill with `Objective::WinProbability`
+    pub samples: usize,
+
+    // Number of threads searching in parallel
+    pub threads: usize,
+
+    // Maximum number of nodes recorded into the search tree, 0 disables recording
+    pub record_nodes: usize,
+
+    pub constraints: RotationConstraints,
+}
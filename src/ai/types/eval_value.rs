@@ -1,10 +1,63 @@
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 
-#[derive(Debug, PartialEq)]
+// `resources` is the fraction of HP and MP the player has left (between 0 and 2),
+// and is used to break ties
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum EvalValue {
-    Won,
+    Won {
+        turn: i32,
+        resources: f64,
+    },
     Lost,
-    InProgress(f64),
+
+    // The line didn't use `skill` before `turn`, as `RotationConstraints::deadlines` requires.
+    // Better than losing, but worse than any line that meets the deadlines.
+    MissedDeadline {
+        skill: String,
+        turn: i32,
+    },
+
+    InProgress {
+        score: f64,
+        resources: f64,
+    },
+
+    // Several random rollouts of a skill, with `Objective::WinProbability`.
+    // `won` and `unfinished` are the shares of the rollouts that were won and that were still in
+    // progress at the end of the search, `estimate` is the heuristic score of the unfinished ones.
+    Sampled {
+        won: f64,
+        unfinished: f64,
+        estimate: f64,
+        resources: f64,
+    },
+}
+
+impl EvalValue {
+    // The score of a fight that isn't decided, used to compare it with other undecided fights
+    pub fn score(&self) -> Option<f64> {
+        match self {
+            EvalValue::Won { .. } | EvalValue::Lost | EvalValue::MissedDeadline { .. } => None,
+            EvalValue::InProgress { score, .. } => Some(*score),
+            EvalValue::Sampled {
+                won,
+                unfinished,
+                estimate,
+                ..
+            } => Some(won + unfinished * estimate),
+        }
+    }
+
+    fn resources(&self) -> f64 {
+        match self {
+            EvalValue::Won { resources, .. }
+            | EvalValue::InProgress { resources, .. }
+            | EvalValue::Sampled { resources, .. } => *resources,
+            EvalValue::Lost | EvalValue::MissedDeadline { .. } => 0.0,
+        }
+    }
 }
 
 impl PartialOrd for EvalValue {
@@ -13,14 +66,61 @@ impl PartialOrd for EvalValue {
             return Some(Ordering::Equal);
         }
 
-        Option::from(match self {
-            EvalValue::Won => Ordering::Greater,
-            EvalValue::Lost => Ordering::Less,
-            EvalValue::InProgress(val_1) => match other {
-                EvalValue::Won => Ordering::Less,
-                EvalValue::Lost => Ordering::Greater,
-                EvalValue::InProgress(val_2) => val_1.total_cmp(val_2),
-            },
+        Some(match (self, other) {
+            // Faster wins are better
+            (
+                EvalValue::Won {
+                    turn: turn_1,
+                    resources: resources_1,
+                },
+                EvalValue::Won {
+                    turn: turn_2,
+                    resources: resources_2,
+                },
+            ) => turn_2.cmp(turn_1).then(resources_1.total_cmp(resources_2)),
+            (EvalValue::Won { .. }, _) => Ordering::Greater,
+            (_, EvalValue::Won { .. }) => Ordering::Less,
+            (EvalValue::Lost, _) => Ordering::Less,
+            (_, EvalValue::Lost) => Ordering::Greater,
+            (EvalValue::MissedDeadline { .. }, EvalValue::MissedDeadline { .. }) => Ordering::Equal,
+            (EvalValue::MissedDeadline { .. }, _) => Ordering::Less,
+            (_, EvalValue::MissedDeadline { .. }) => Ordering::Greater,
+            _ => self
+                .score()
+                .unwrap_or_default()
+                .total_cmp(&other.score().unwrap_or_default())
+                .then(self.resources().total_cmp(&other.resources())),
         })
     }
 }
+
+impl Display for EvalValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalValue::Won { turn, .. } => write!(f, "Won on turn {}", turn),
+            EvalValue::Lost => write!(f, "Lost"),
+            EvalValue::MissedDeadline { skill, turn } => {
+                write!(f, "Misses the deadline of {} (turn {})", skill, turn)
+            }
+            EvalValue::InProgress { score, .. } => write!(f, "{:.2}", score),
+            EvalValue::Sampled {
+                won,
+                unfinished,
+                estimate,
+                ..
+            } => {
+                write!(f, "{:.1}% of rollouts won", 100.0 * won)?;
+                if *unfinished > 0.0 {
+                    write!(
+                        f,
+                        ", {:.1}% unfinished (heuristic {:.1}%)",
+                        100.0 * unfinished,
+                        100.0 * estimate
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,2 +1,9 @@
+pub mod ai_settings;
 pub mod eval_value;
+pub mod objective;
+pub mod rotation_constraints;
+pub mod search_progress;
+pub mod search_stats;
+pub mod search_tree;
 pub mod skill_eval;
+pub mod state_summary;
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+// What the AI tries to optimize in positions where the fight isn't over yet.
+// Won fights are always preferred, faster wins first.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum Objective {
+    // Minimize the remaining HP of the enemies
+    EnemyHealth,
+
+    // Minimize the expected number of turns until the enemies die,
+    // projected from the damage and mana per turn so far.
+    // Saved options may still have the damage per turn objective, which ranked lines like `EnemyHealth`.
+    #[serde(alias = "DamagePerTurn")]
+    TurnsToKill,
+
+    // Maximize the probability of winning, measured by sampling several rollouts of every first skill.
+    // Every rollout plays the line its search found again, with new random outcomes.
+    // Rollouts that aren't over at the end of the line are scored by a heuristic.
+    WinProbability,
+}
+
+impl Objective {
+    pub fn vec() -> Vec<Objective> {
+        vec![
+            Objective::EnemyHealth,
+            Objective::TurnsToKill,
+            Objective::WinProbability,
+        ]
+    }
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Objective::EnemyHealth => "Enemy Health",
+                Objective::TurnsToKill => "Turns to Kill",
+                Objective::WinProbability => "Win Probability",
+            }
+        )
+    }
+}
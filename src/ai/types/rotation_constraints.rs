@@ -0,0 +1,292 @@
+use crate::game::entities::player::Player;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkillLimit {
+    pub skill: String,
+    pub uses: i32,
+}
+
+// `skill` has to be used before `turn`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkillDeadline {
+    pub skill: String,
+    pub turn: i32,
+}
+
+impl SkillDeadline {
+    fn is_met(&self, player: &Player) -> bool {
+        player
+            .skills
+            .iter()
+            .any(|skill| skill.name == self.skill && skill.uses > 0)
+    }
+
+    // Whether the skill wasn't used in time, once `turn` (starting from 1) is reached
+    pub fn is_missed(&self, player: &Player, turn: i32) -> bool {
+        turn >= self.turn && !self.is_met(player)
+    }
+}
+
+// Restrictions on the skills the AI may use, skills are referred to by name
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RotationConstraints {
+    // Skills that are used first, in order
+    pub opener: Vec<String>,
+
+    pub forbidden: Vec<String>,
+
+    // Skills that cost mana can't take the player below this much MP
+    pub min_mp: i32,
+
+    pub max_uses: Vec<SkillLimit>,
+    pub deadlines: Vec<SkillDeadline>,
+}
+
+impl RotationConstraints {
+    // Keeps the skills in `skills` that `player` may use on `turn` (starting from 1).
+    // The opener and deadlines only narrow down the skills when one of their skills is allowed.
+    pub fn filter(&self, player: &Player, turn: i32, skills: Vec<usize>) -> Vec<usize> {
+        let allowed: Vec<usize> = skills
+            .into_iter()
+            .filter(|&index| {
+                let skill = &player.skills[index];
+
+                !self.forbidden.contains(&skill.name)
+                    && (skill.mana == 0 || player.base.mp - skill.mana >= self.min_mp)
+                    && self
+                        .max_uses
+                        .iter()
+                        .all(|limit| limit.skill != skill.name || skill.uses < limit.uses)
+            })
+            .collect();
+
+        let only = |name: &str| -> Vec<usize> {
+            allowed
+                .iter()
+                .copied()
+                .filter(|&index| player.skills[index].name == name)
+                .collect()
+        };
+
+        if let Some(name) = self.opener.get((turn - 1) as usize) {
+            let opener = only(name);
+            if !opener.is_empty() {
+                return opener;
+            }
+        }
+
+        // A skill that wasn't used yet has to be used on the last turn before its deadline.
+        // The search treats lines that miss it as failures, this only saves searching them.
+        for deadline in &self.deadlines {
+            if !deadline.is_met(player) && turn == deadline.turn - 1 {
+                let forced = only(&deadline.skill);
+                if !forced.is_empty() {
+                    return forced;
+                }
+            }
+        }
+
+        allowed
+    }
+
+    // The first deadline that `player` missed by `turn`
+    pub fn missed_deadline(&self, player: &Player, turn: i32) -> Option<&SkillDeadline> {
+        self.deadlines
+            .iter()
+            .find(|deadline| deadline.is_missed(player, turn))
+    }
+
+    // Describes the constraints that can't be met on `turn`, given the `skills` the player can use
+    pub fn unmet(&self, player: &Player, turn: i32, skills: Vec<usize>) -> Vec<String> {
+        let allowed = self.filter(player, turn, skills);
+        let mut unmet = Vec::new();
+
+        if allowed.is_empty() {
+            unmet.push("No skill meets the constraints, so any skill may be used".to_string());
+        }
+
+        if let Some(name) = self.opener.get((turn - 1) as usize) {
+            if !allowed
+                .iter()
+                .any(|&index| &player.skills[index].name == name)
+            {
+                unmet.push(format!("{} can't be used as opener skill {}", name, turn));
+            }
+        }
+
+        for deadline in &self.deadlines {
+            if deadline.is_missed(player, turn) {
+                unmet.push(format!(
+                    "{} wasn't used before turn {}",
+                    deadline.skill, deadline.turn
+                ));
+            }
+        }
+
+        unmet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::types::dict::Dict;
+    use crate::game::types::skill::Skill;
+
+    // Player with 100 MP
+    fn player() -> Player {
+        Player::new(
+            Dict::new(),
+            1,
+            "fire",
+            vec![
+                Skill::new("Attack", 0, 0),
+                Skill::new("Fury", 40, 2),
+                Skill::new("Curse", 20, 3),
+            ],
+        )
+    }
+
+    fn names(player: &Player, skills: Vec<usize>) -> Vec<&str> {
+        skills
+            .into_iter()
+            .map(|index| player.skills[index].name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn opener_respects_forbidden_skills_and_min_mp() {
+        let player = player();
+        let constraints = RotationConstraints {
+            opener: vec!["Curse".to_string(), "Fury".to_string()],
+            forbidden: vec!["Curse".to_string()],
+            min_mp: 70,
+            ..Default::default()
+        };
+
+        // Curse is forbidden and Fury would go below the min MP, so the opener is skipped
+        assert_eq!(
+            names(&player, constraints.filter(&player, 1, vec![0, 1, 2])),
+            ["Attack"]
+        );
+        assert_eq!(
+            names(&player, constraints.filter(&player, 2, vec![0, 1, 2])),
+            ["Attack"]
+        );
+
+        let constraints = RotationConstraints {
+            opener: vec!["Curse".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&player, constraints.filter(&player, 1, vec![0, 1, 2])),
+            ["Curse"]
+        );
+        assert_eq!(
+            names(&player, constraints.filter(&player, 2, vec![0, 1, 2])),
+            ["Attack", "Fury", "Curse"]
+        );
+    }
+
+    #[test]
+    fn max_uses_and_min_mp() {
+        let mut player = player();
+        let constraints = RotationConstraints {
+            min_mp: 50,
+            max_uses: vec![SkillLimit {
+                skill: "Curse".to_string(),
+                uses: 1,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&player, constraints.filter(&player, 1, vec![0, 1, 2])),
+            ["Attack", "Fury", "Curse"]
+        );
+
+        player.use_skill(2);
+        assert_eq!(player.base.mp, 80);
+        assert_eq!(
+            names(&player, constraints.filter(&player, 2, vec![0, 1])),
+            ["Attack"]
+        );
+    }
+
+    #[test]
+    fn deadline_only_applies_on_its_last_turn() {
+        let mut player = player();
+        let constraints = RotationConstraints {
+            opener: vec!["Attack".to_string()],
+            deadlines: vec![SkillDeadline {
+                skill: "Curse".to_string(),
+                turn: 4,
+            }],
+            ..Default::default()
+        };
+
+        // The opener comes first, and the deadline isn't close yet
+        assert_eq!(
+            names(&player, constraints.filter(&player, 1, vec![0, 1, 2])),
+            ["Attack"]
+        );
+        assert_eq!(
+            names(&player, constraints.filter(&player, 2, vec![0, 1, 2])),
+            ["Attack", "Fury", "Curse"]
+        );
+        assert_eq!(
+            names(&player, constraints.filter(&player, 3, vec![0, 1, 2])),
+            ["Curse"]
+        );
+
+        // Curse was on cooldown on its last turn, so the deadline was missed
+        assert_eq!(
+            names(&player, constraints.filter(&player, 3, vec![0, 1])),
+            ["Attack", "Fury"]
+        );
+        assert_eq!(
+            names(&player, constraints.filter(&player, 4, vec![0, 1, 2])),
+            ["Attack", "Fury", "Curse"]
+        );
+
+        // Used skills don't have to be used again
+        player.use_skill(2);
+        assert_eq!(
+            names(&player, constraints.filter(&player, 3, vec![0, 1])),
+            ["Attack", "Fury"]
+        );
+    }
+
+    #[test]
+    fn missed_deadlines_are_reported() {
+        let mut player = player();
+        let constraints = RotationConstraints {
+            opener: vec!["Fury".to_string()],
+            deadlines: vec![SkillDeadline {
+                skill: "Curse".to_string(),
+                turn: 3,
+            }],
+            ..Default::default()
+        };
+
+        assert!(constraints.missed_deadline(&player, 2).is_none());
+        assert!(constraints.unmet(&player, 2, vec![0, 1, 2]).is_empty());
+        assert_eq!(
+            constraints.missed_deadline(&player, 3).unwrap().skill,
+            "Curse"
+        );
+
+        // Fury is on cooldown, so the opener can't be used either
+        assert_eq!(
+            constraints.unmet(&player, 1, vec![0, 2]),
+            ["Fury can't be used as opener skill 1"]
+        );
+        assert_eq!(
+            constraints.unmet(&player, 3, vec![0, 1, 2]),
+            ["Curse wasn't used before turn 3"]
+        );
+
+        player.use_skill(2);
+        assert!(constraints.missed_deadline(&player, 3).is_none());
+    }
+}
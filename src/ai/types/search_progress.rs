@@ -0,0 +1,21 @@
+use crate::ai::types::eval_value::EvalValue;
+use std::time::Duration;
+
+// A snapshot of a search that is still running
+#[derive(Debug, Clone)]
+pub struct SearchProgress {
+    // The depth currently being searched, out of the maximum depth
+    pub depth: u8,
+    pub max_depth: u8,
+
+    // Nodes searched at the current depth, and the time since the search started
+    pub nodes: u64,
+    pub elapsed: Duration,
+
+    // The best first skill found so far, with its evaluation
+    pub best_skill: Option<(String, EvalValue)>,
+
+    // Number of first skills evaluated at the current depth, and the total number of first skills
+    pub skills_done: usize,
+    pub skills_total: usize,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Nodes searched by the deepest search of a turn, and how long it took
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    pub fn nodes_per_second(&self) -> f64 {
+        self.nodes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
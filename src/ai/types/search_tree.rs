@@ -0,0 +1,22 @@
+use crate::ai::types::eval_value::EvalValue;
+use crate::ai::types::state_summary::StateSummary;
+use serde::{Deserialize, Serialize};
+
+// A node of a recorded search tree
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchNode {
+    // The skill that led to this node, `None` for the root
+    pub skill: Option<String>,
+
+    pub state: StateSummary,
+    pub eval: EvalValue,
+
+    // The recorded children, a node might have more children that weren't recorded
+    pub children: Vec<SearchNode>,
+}
+
+impl SearchNode {
+    pub fn size(&self) -> usize {
+        1 + self.children.iter().map(SearchNode::size).sum::<usize>()
+    }
+}
@@ -1,20 +1,53 @@
 use crate::ai::types::eval_value::EvalValue;
+use crate::ai::types::search_tree::SearchNode;
+use crate::ai::types::state_summary::StateSummary;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
-#[derive(Debug)]
+// A skill in a planned line, along with the state the AI expects after using it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlannedSkill {
+    pub index: usize,
+    pub name: String,
+    pub state: StateSummary,
+
+    // The evaluation of the line starting with this skill
+    pub eval: EvalValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SkillEval {
     pub index: Option<usize>,
     pub eval: EvalValue,
+
+    // The principal variation, starting with the skill at `index`
+    pub line: Vec<PlannedSkill>,
+
+    // The explored subtree, if it was recorded
+    pub tree: Option<Box<SearchNode>>,
 }
 
 impl SkillEval {
     pub fn new(index: Option<usize>, eval: EvalValue) -> Self {
-        SkillEval { index, eval }
+        SkillEval {
+            index,
+            eval,
+            line: Vec::new(),
+            tree: None,
+        }
     }
 
     pub fn lost() -> Self {
         SkillEval::new(None, EvalValue::Lost)
     }
+
+    // Prepends `skill` to the line of `self`, making it the first skill of the line
+    pub fn with_first_skill(mut self, skill: PlannedSkill) -> Self {
+        self.index = Some(skill.index);
+        self.line.insert(0, skill);
+
+        self
+    }
 }
 
 impl PartialEq<Self> for SkillEval {
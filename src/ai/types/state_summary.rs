@@ -0,0 +1,80 @@
+use crate::game::entities::entity::Entity;
+use crate::game::game_manager::GameManager;
+use serde::{Deserialize, Serialize};
+
+// A lightweight snapshot of an entity, used to display predicted states
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntitySummary {
+    pub name: String,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub mp: i32,
+    pub max_mp: i32,
+    pub effects: Vec<String>,
+}
+
+impl From<&Entity> for EntitySummary {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            name: entity.name.clone(),
+            hp: entity.hp,
+            max_hp: entity.max_hp,
+            mp: entity.mp,
+            max_mp: entity.max_mp,
+            effects: entity.effects.iter().map(|eff| eff.name.clone()).collect(),
+        }
+    }
+}
+
+// A lightweight snapshot of a game, used to display predicted states
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateSummary {
+    pub turn: i32,
+    pub player: EntitySummary,
+    pub enemies: Vec<EntitySummary>,
+}
+
+impl From<&GameManager> for StateSummary {
+    fn from(game: &GameManager) -> Self {
+        Self {
+            turn: game.turn,
+            player: EntitySummary::from(game.player.get_base_entity()),
+            enemies: game
+                .enemies
+                .iter()
+                .map(|enemy| EntitySummary::from(enemy.get_base_entity()))
+                .collect(),
+        }
+    }
+}
+
+impl StateSummary {
+    pub fn enemies_hp_percent(&self) -> f64 {
+        let mut hp = 0.0;
+        let mut max_hp = 0.0;
+        for enemy in &self.enemies {
+            hp += enemy.hp.max(0) as f64;
+            max_hp += enemy.max_hp as f64;
+        }
+
+        100.0 * hp / max_hp
+    }
+
+    // Effects that weren't on their entity in `before`, e.g. `Stun on Dummy`
+    pub fn new_effects(&self, before: &StateSummary) -> Vec<String> {
+        let entities = [(&self.player, &before.player)]
+            .into_iter()
+            .chain(self.enemies.iter().zip(&before.enemies));
+
+        let mut effects = Vec::new();
+        for (entity, entity_before) in entities {
+            for effect in &entity.effects {
+                if !entity_before.effects.contains(effect) {
+                    effects.push(format!("{} on {}", effect, entity.name));
+                }
+            }
+        }
+
+        effects
+    }
+}
@@ -1,10 +1,11 @@
 use crate::game::entities::entity::EntityTrait;
 use crate::game::types::dict::Dict;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 pub mod pirate;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone)]
 pub enum Armor {
     Pirate,
 }
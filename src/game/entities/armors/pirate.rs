@@ -1,17 +1,19 @@
 use crate::game::entities::entity::EntityMutRef::PlayerMutRef;
 use crate::game::entities::entity::EntityRef::PlayerRef;
-use crate::game::entities::entity::{Entity, EntityMutRef, EntityRef, EntityTrait};
+use crate::game::entities::entity::{Entity, EntityMutRef, EntityRef, EntitySnapshot, EntityTrait};
 use crate::game::entities::player::Player;
+use crate::game::types::counter::Counter;
 use crate::game::types::damage::DamageRange;
 use crate::game::types::dict::Dict;
 use crate::game::types::effects::{DoT, Effect, Stun};
 use crate::game::types::hit::Hit;
 use crate::game::types::skill::Skill;
+use serde::{Deserialize, Serialize};
 
 /// The Pirate armor
 ///
 /// See https://dragonfable-endgame.fandom.com/wiki/Pirate for details
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Pirate {
     base: Player,
 
@@ -369,7 +371,8 @@ impl Pirate {
     }
 
     fn skill_cursed_treasure(&mut self, entity: &mut Entity) {
-        self.plunder += 1;
+        // The editor can set Plunder to its max
+        self.plunder = self.plunder.saturating_add(1);
         self.cursed_treasure = true;
         self.base.base.add_effect(Effect::new(
             "Cursed Treasure",
@@ -444,14 +447,18 @@ impl EntityTrait for Pirate {
         }
     }
 
-    fn get_base_type(&self) -> EntityRef {
+    fn get_base_type(&self) -> EntityRef<'_> {
         PlayerRef(&self.base)
     }
 
-    fn get_base_type_mut(&mut self) -> EntityMutRef {
+    fn get_base_type_mut(&mut self) -> EntityMutRef<'_> {
         PlayerMutRef(&mut self.base)
     }
 
+    fn snapshot(&self) -> EntitySnapshot {
+        EntitySnapshot::Pirate(self.clone())
+    }
+
     fn setup(
         &mut self,
         _player: Option<&mut Box<dyn EntityTrait + Send>>,
@@ -461,4 +468,21 @@ impl EntityTrait for Pirate {
             .get_base_entity_mut()
             .add_effect(self.opening_effect());
     }
+
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            Counter::new("Plunder", self.plunder as i32, i8::MAX as i32),
+            Counter::new("Retaliation", self.retaliation as i32, 1),
+            Counter::new("Cursed Treasure", self.cursed_treasure as i32, 1),
+        ]
+    }
+
+    fn set_counter(&mut self, name: &str, value: i32) {
+        match name {
+            "Plunder" => self.plunder = value as i8,
+            "Retaliation" => self.retaliation = value != 0,
+            "Cursed Treasure" => self.cursed_treasure = value != 0,
+            _ => {}
+        }
+    }
 }
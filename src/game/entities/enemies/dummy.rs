@@ -1,10 +1,11 @@
 use crate::game::entities::enemy::Enemy;
 use crate::game::entities::entity::EntityMutRef::EnemyMutRef;
 use crate::game::entities::entity::EntityRef::EnemyRef;
-use crate::game::entities::entity::{Entity, EntityMutRef, EntityRef, EntityTrait};
+use crate::game::entities::entity::{Entity, EntityMutRef, EntityRef, EntitySnapshot, EntityTrait};
 use crate::game::types::damage::{DamageRange, DamageType};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Dummy {
     base: Enemy,
 }
@@ -29,13 +30,17 @@ impl EntityTrait for Dummy {
     ) {
     }
 
-    fn get_base_type(&self) -> EntityRef {
+    fn get_base_type(&self) -> EntityRef<'_> {
         EnemyRef(&self.base)
     }
 
-    fn get_base_type_mut(&mut self) -> EntityMutRef {
+    fn get_base_type_mut(&mut self) -> EntityMutRef<'_> {
         EnemyMutRef(&mut self.base)
     }
+
+    fn snapshot(&self) -> EntitySnapshot {
+        EntitySnapshot::Dummy(self.clone())
+    }
 }
 
 impl Dummy {
@@ -1,9 +1,10 @@
 use crate::game::entities::entity::EntityTrait;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 pub mod dummy;
 
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone)]
 pub enum Challenge {
     Dummy,
 }
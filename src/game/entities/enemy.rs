@@ -1,8 +1,9 @@
 use crate::game::entities::entity::{Entity, EntityType};
 use crate::game::types::damage::{DamageRange, DamageType};
 use crate::game::types::dict::Dict;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Enemy {
     pub base: Entity,
 }
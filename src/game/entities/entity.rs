@@ -1,13 +1,22 @@
+use crate::game::entities::armors::pirate::Pirate;
+use crate::game::entities::enemies::dummy::Dummy;
 use crate::game::entities::enemy::Enemy;
 use crate::game::entities::entity::EntityType::PlayerType;
 use crate::game::entities::player::Player;
+use crate::game::types::counter::Counter;
 use crate::game::types::damage::{DamageRange, DamageType};
 use crate::game::types::dict::Dict;
 use crate::game::types::effects::{DoT, Effect, Stun};
-use crate::game::types::hit::Hit;
+use crate::game::types::hit::{ForcedHit, Hit, HitOutcome};
 use crate::game::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
-#[derive(Debug, Clone)]
+// Gear can only give 80% resistance to an element, and 100 crit
+pub const GEAR_RESIST_CAP: f32 = 80.0;
+pub const GEAR_CRIT_CAP: f32 = 100.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum EntityType {
     PlayerType,
     EnemyType,
@@ -68,9 +77,11 @@ pub trait EntityTrait {
         enemies: &mut Vec<Box<dyn EntityTrait + Send>>,
     );
 
-    fn get_base_type(&self) -> EntityRef;
+    fn get_base_type(&self) -> EntityRef<'_>;
+
+    fn get_base_type_mut(&mut self) -> EntityMutRef<'_>;
 
-    fn get_base_type_mut(&mut self) -> EntityMutRef;
+    fn snapshot(&self) -> EntitySnapshot;
 
     fn setup(
         &mut self,
@@ -78,6 +89,14 @@ pub trait EntityTrait {
         _enemies: &mut Vec<Box<dyn EntityTrait + Send>>,
     ) {
     }
+
+    // Armor-specific state, so it can be edited in the middle of a fight.
+    // Setting a counter the entity doesn't have does nothing.
+    fn get_counters(&self) -> Vec<Counter> {
+        Vec::new()
+    }
+
+    fn set_counter(&mut self, _name: &str, _value: i32) {}
 }
 
 impl Clone for Box<dyn EntityTrait + Send> {
@@ -86,7 +105,23 @@ impl Clone for Box<dyn EntityTrait + Send> {
     }
 }
 
-#[derive(Debug, Clone)]
+// A serializable copy of an entity, used to send games to other threads and workers
+#[derive(Serialize, Deserialize, Clone)]
+pub enum EntitySnapshot {
+    Pirate(Pirate),
+    Dummy(Dummy),
+}
+
+impl EntitySnapshot {
+    pub fn restore(self) -> Box<dyn EntityTrait + Send> {
+        match self {
+            EntitySnapshot::Pirate(pirate) => Box::new(pirate),
+            EntitySnapshot::Dummy(dummy) => Box::new(dummy),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Entity {
     pub max_hp: i32,
     pub hp: i32,
@@ -114,6 +149,19 @@ pub struct Entity {
 
     // Number of stuns currently active on the entity
     stuns: usize,
+
+    // Outcomes used instead of the next rolls, for the entity's hits and for stuns on the entity.
+    // `None` rolls randomly. They only apply to the current turn, so they aren't part of the state.
+    #[serde(skip)]
+    pub forced_hits: VecDeque<Option<ForcedHit>>,
+    #[serde(skip)]
+    pub forced_stuns: VecDeque<Option<bool>>,
+
+    // Number of hit and stun outcomes decided so far, used to find out how many a turn needs
+    #[serde(skip)]
+    pub hit_rolls: usize,
+    #[serde(skip)]
+    pub stun_rolls: usize,
 }
 
 impl Entity {
@@ -148,6 +196,10 @@ impl Entity {
             gear_resists: Dict::new(),
             effects,
             stuns: 0,
+            forced_hits: VecDeque::new(),
+            forced_stuns: VecDeque::new(),
+            hit_rolls: 0,
+            stun_rolls: 0,
         }
     }
 
@@ -158,8 +210,8 @@ impl Entity {
             ((value + stat_diff.get(stat)) / divide_by).floor() - (value / divide_by).floor()
         };
 
-        self.max_hp += self.get_bonus("END") as i32 * 5;
-        self.max_mp += self.get_bonus("WIS") as i32 * 5;
+        self.max_hp += stat_diff.get("END") as i32 * 5;
+        self.max_mp += stat_diff.get("WIS") as i32 * 5;
 
         self.bonuses
             .add("crit", get_floored_value_diff(self, "LUK", 10.0));
@@ -179,117 +231,198 @@ impl Entity {
             .add("health", get_floored_value_diff(self, "WIS", -5.0));
     }
 
-    pub fn get_resist(&self, elem: &str) -> f32 {
-        let mut resist = self.resists.get(elem);
+    // The resistance given by gear, before `GEAR_RESIST_CAP`
+    pub fn get_gear_resist(&self, elem: &str) -> f32 {
         let mut gear_resist = self.gear_resists.get(elem);
+        if elem != "null" && elem != "all" {
+            gear_resist += self.gear_resists.get("all");
+        }
 
-        if elem != "null" {
+        gear_resist
+    }
+
+    pub fn get_resist(&self, elem: &str) -> f32 {
+        let mut resist = self.resists.get(elem);
+        if elem != "null" && elem != "all" {
             resist += self.resists.get("all");
-            gear_resist += self.gear_resists.get("all")
         }
 
-        resist + gear_resist.min(80.0)
+        resist + self.get_gear_resist(elem).min(GEAR_RESIST_CAP)
+    }
+
+    // The most of `bonus` that gear can give
+    pub fn get_gear_bonus_cap(bonus: &str) -> Option<f32> {
+        match bonus {
+            "crit" => Some(GEAR_CRIT_CAP),
+            _ => None,
+        }
     }
 
     pub fn get_bonus(&self, bonus: &str) -> f32 {
         let value = self.bonuses.get(bonus);
         let mut gear_value = self.gear_bonuses.get(bonus);
 
-        // Gear can only give 100 crit
-        if bonus == "crit" {
-            gear_value = gear_value.max(100.0);
+        if let Some(cap) = Self::get_gear_bonus_cap(bonus) {
+            gear_value = gear_value.min(cap);
         }
 
         value + gear_value
     }
 
-    pub fn attack(&mut self, other: &mut Entity, hits: Vec<Hit>, attack_mana: bool) {
-        for hit in hits {
-            if let Some(bonuses) = &hit.bonuses {
-                self.bonuses.merge(bonuses);
-            }
+    // Chance of a hit to miss `other`
+    fn miss_chance(&self, other: &Entity) -> f32 {
+        (other.get_bonus(self.dmg_type.get_mpm_str()) - self.get_bonus("bonus")) / 151.0
+    }
 
-            // We first check if the hit is a miss
-            let mpm_value =
-                other.bonuses.get(self.dmg_type.get_mpm_str()) - self.bonuses.get("bonus");
+    // Chance of a hit that didn't miss `other` to be a glancing blow
+    fn glance_chance(&self, other: &Entity) -> f32 {
+        (other.get_bonus(self.dmg_type.get_bpd_str()) - self.get_bonus("bonus")) / 151.0
+    }
 
-            if utils::chance(mpm_value / 151.0) {
-                continue; // Hit missed, continue to the next hit
-            }
+    fn crit_chance(&self) -> f32 {
+        self.get_bonus("crit") / 201.0 // Crit rolls are between 0-200 inclusive, so we need to divide by 201
+    }
 
-            let bpd_value =
-                other.bonuses.get(self.dmg_type.get_bpd_str()) - self.bonuses.get("bonus");
-            let bpd = utils::chance(bpd_value / 151.0);
+    // The damage of a hit before its modifiers, with the mainstat addition
+    fn add_mainstat_damage(&self, dmg: f32, dmg_type: DamageType) -> f32 {
+        let mainstat = self.get_stat_by_dmg_type(dmg_type);
 
-            let crit = utils::chance(self.bonuses.get("crit") / 201.0); // Crit rolls are between 0-200 inclusive, so we need to divide by 201
+        // If `mainstat` is negative, the damage addition is rounded up instead of down
+        if mainstat >= 0.0 {
+            dmg + (mainstat / 10.0).floor()
+        } else {
+            dmg + (mainstat / 10.0).ceil()
+        }
+    }
 
-            if let Some(effects) = &hit.before_hit_effects {
-                if !bpd || crit {
-                    // Hit was a success, we can apply the before-hit effects
-                    for eff in effects {
-                        other.add_effect((*eff).clone());
-                    }
-                }
+    fn hit_modifier(&self, crit: bool, bpd: bool, attack_mana: bool) -> f32 {
+        let mut hit_modifier: f32;
+        if attack_mana && !crit && bpd {
+            hit_modifier = 0.0; // Non-crit glancing mana hits result in 0 damage
+                                // TODO: Check whether critical glancing mana hits result in 0 damage
+        } else if crit && !bpd {
+            // INT boost
+            hit_modifier = self.get_bonus("crit_modifier_bonus") + 1.75;
+            hit_modifier *= 1.0 + self.get_bonus("INT") / 1000.0;
+        } else {
+            if !crit && bpd {
+                // Glancing blow
+                hit_modifier = 0.05;
+            } else {
+                // Normal hit or critical glancing blow
+                hit_modifier = 1.0;
             }
 
-            // We save the damage as f32 and round it to i32 before applying it
-            let mut dmg = hit.dmg_range.get();
+            // STR boost
+            hit_modifier *= 1.0 + self.get_bonus("STR") / 1000.0;
+        }
 
-            let mainstat = self.bonuses.get_by_dmg_type(hit.dmg_type);
+        hit_modifier * (1.0 + self.get_bonus("DEX") / 4000.0) // DEX boost
+    }
 
-            // If `mainstat` is negative, the damage addition is rounded up instead of down
-            if mainstat >= 0.0 {
-                dmg += (mainstat / 10.0).floor();
-            } else {
-                dmg += (mainstat / 10.0).ceil();
+    // Boost and `other`'s resistance
+    fn damage_multiplier(&self, other: &Entity, elem: &str) -> f32 {
+        (1.0 + self.get_bonus("boost") / 100.0) * (100.0 - other.get_resist(elem)) / 100.0
+    }
+
+    // The average damage of `hit` against `other`, ignoring its effects and bonuses
+    pub fn expected_damage(&self, other: &Entity, hit: &Hit, attack_mana: bool) -> f32 {
+        let hit_chance = 1.0 - self.miss_chance(other).clamp(0.0, 1.0);
+        let bpd_chance = self.glance_chance(other).clamp(0.0, 1.0);
+        let crit_chance = self.crit_chance().clamp(0.0, 1.0);
+
+        let dmg =
+            self.add_mainstat_damage((hit.dmg_range.min + hit.dmg_range.max) / 2.0, hit.dmg_type);
+
+        let mut hit_modifier = 0.0;
+        for (crit, crit_weight) in [(true, crit_chance), (false, 1.0 - crit_chance)] {
+            for (bpd, bpd_weight) in [(true, bpd_chance), (false, 1.0 - bpd_chance)] {
+                hit_modifier +=
+                    crit_weight * bpd_weight * self.hit_modifier(crit, bpd, attack_mana);
             }
+        }
 
-            let mut hit_modifier: f32;
-            if attack_mana && !crit && bpd {
-                hit_modifier = 0.0; // Non-crit glancing mana hits result in 0 damage
-                                    // TODO: Check whether critical glancing mana hits result in 0 damage
-            } else if crit && !bpd {
-                // INT boost
-                hit_modifier = self.bonuses.get("crit_modifier_bonus") + 1.75;
-                hit_modifier *= 1.0 + self.bonuses.get("INT") / 1000.0;
-            } else {
-                if !crit && bpd {
-                    // Glancing blow
-                    hit_modifier = 0.05;
-                } else {
-                    // Normal hit or critical glancing blow
-                    hit_modifier = 1.0;
-                }
+        hit_chance * dmg * hit_modifier * self.damage_multiplier(other, &hit.elem)
+    }
+
+    // The average damage of a single hit with 100% weapon damage against `other`
+    pub fn expected_attack_damage(&mut self, other: &Entity) -> f32 {
+        let hits = self.generate_hits(1, 1.0, None, None, None);
 
-                // STR boost
-                hit_modifier *= 1.0 + self.bonuses.get("STR") / 1000.0;
+        hits.iter()
+            .map(|hit| self.expected_damage(other, hit, false))
+            .sum()
+    }
+
+    pub fn attack(&mut self, other: &mut Entity, hits: Vec<Hit>, attack_mana: bool) {
+        for hit in hits {
+            if let Some(bonuses) = &hit.bonuses {
+                self.bonuses.merge(bonuses);
             }
 
-            hit_modifier *= 1.0 + self.bonuses.get("DEX") / 4000.0; // DEX boost
-            dmg *= hit_modifier;
+            self.attack_hit(other, &hit, attack_mana);
 
-            dmg *= 1.0 + self.bonuses.get("boost") / 100.0; // Boost
+            if let Some(bonuses) = &hit.bonuses {
+                self.bonuses.unmerge(bonuses);
+            }
+        }
+    }
 
-            // Resistances
-            dmg *= (100.0 - self.get_resist(&hit.elem)) / 100.0;
+    fn attack_hit(&mut self, other: &mut Entity, hit: &Hit, attack_mana: bool) {
+        self.hit_rolls += 1;
+        let forced_hit = self.forced_hits.pop_front().flatten();
 
-            dmg = dmg.round();
-            if attack_mana {
-                other.mp -= dmg as i32;
-            } else {
-                other.hp -= dmg as i32;
+        let (bpd, crit) = if let Some(forced_hit) = forced_hit {
+            if forced_hit.outcome == HitOutcome::Miss {
+                return;
             }
 
-            if let Some(effects) = &hit.after_hit_effects {
-                if !bpd || crit {
-                    // Hit was a success, we can apply the after-hit effects
-                    for eff in effects {
-                        other.add_effect((*eff).clone());
-                    }
+            (
+                forced_hit.outcome == HitOutcome::Glance,
+                forced_hit.outcome == HitOutcome::Crit,
+            )
+        } else {
+            // We first check if the hit is a miss
+            if utils::chance(self.miss_chance(other)) {
+                return;
+            }
+
+            (
+                utils::chance(self.glance_chance(other)),
+                utils::chance(self.crit_chance()),
+            )
+        };
+
+        if let Some(effects) = &hit.before_hit_effects {
+            if !bpd || crit {
+                // Hit was a success, we can apply the before-hit effects
+                for eff in effects {
+                    other.add_effect((*eff).clone());
                 }
             }
-            if let Some(bonuses) = &hit.bonuses {
-                self.bonuses.unmerge(bonuses);
+        }
+
+        // We save the damage as f32 and round it to i32 before applying it
+        let mut dmg = self.add_mainstat_damage(hit.dmg_range.get(), hit.dmg_type);
+        dmg *= self.hit_modifier(crit, bpd, attack_mana);
+        dmg *= self.damage_multiplier(other, &hit.elem);
+
+        dmg = dmg.round();
+        if let Some(damage) = forced_hit.and_then(|forced_hit| forced_hit.damage) {
+            dmg = damage as f32;
+        }
+        if attack_mana {
+            other.mp -= dmg as i32;
+        } else {
+            other.hp -= dmg as i32;
+        }
+
+        if let Some(effects) = &hit.after_hit_effects {
+            if !bpd || crit {
+                // Hit was a success, we can apply the after-hit effects
+                for eff in effects {
+                    other.add_effect((*eff).clone());
+                }
             }
         }
     }
@@ -304,7 +437,13 @@ impl Entity {
         if let Some(stun) = &eff.stun {
             match stun {
                 Stun::Normal => {
-                    if !utils::chance(1.0 - (self.get_resist("immobility") / 100.0)) {
+                    self.stun_rolls += 1;
+                    let landed = match self.forced_stuns.pop_front().flatten() {
+                        Some(landed) => landed,
+                        None => utils::chance(1.0 - (self.get_resist("immobility") / 100.0)),
+                    };
+
+                    if !landed {
                         return; // Stun failed, don't apply the effect
                     }
                 }
@@ -357,7 +496,7 @@ impl Entity {
         let mut effects_to_remove: Vec<usize> = Vec::new();
         let mut dots: Vec<DoT> = Vec::new();
 
-        for (index, mut eff) in &mut self.effects.iter_mut().enumerate() {
+        for (index, eff) in &mut self.effects.iter_mut().enumerate() {
             eff.duration -= 1;
             if eff.duration <= 0 {
                 effects_to_remove.push(index);
@@ -420,7 +559,12 @@ impl Entity {
     }
 
     pub fn get_mainstat(&self) -> f32 {
-        self.bonuses.get_by_dmg_type(self.dmg_type)
+        self.get_stat_by_dmg_type(self.dmg_type)
+    }
+
+    // The stat that adds damage to hits of `dmg_type`, with gear
+    pub fn get_stat_by_dmg_type(&self, dmg_type: DamageType) -> f32 {
+        self.bonuses.get_by_dmg_type(dmg_type) + self.gear_bonuses.get_by_dmg_type(dmg_type)
     }
 
     pub fn generate_dot(&self, mut dmg: DamageRange, stat_dmg: bool) -> DoT {
@@ -431,3 +575,130 @@ impl Entity {
         DoT::new(dmg, &self.elem)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entities::entity::EntityType::EnemyType;
+    use crate::game::types::hit::{ForcedHit, HitOutcome};
+
+    fn entity(entity_type: EntityType) -> Entity {
+        Entity::new(
+            1000,
+            1000,
+            "Test".to_string(),
+            1,
+            entity_type,
+            DamageRange::from(100.0),
+            DamageType::Constant,
+            "fire".to_string(),
+            Dict::new(),
+            Dict::new(),
+            Vec::new(),
+        )
+    }
+
+    // Attacks `target` once with a forced normal hit, and returns the damage dealt
+    fn normal_hit_damage(attacker: &mut Entity, target: &mut Entity) -> i32 {
+        let hp = target.hp;
+        let hits = attacker.generate_hits(1, 1.0, None, None, None);
+        attacker.forced_hits.push_back(Some(ForcedHit {
+            outcome: HitOutcome::Normal,
+            damage: None,
+        }));
+        attacker.attack(target, hits, false);
+
+        hp - target.hp
+    }
+
+    #[test]
+    fn damage_uses_target_resist() {
+        let mut attacker = entity(PlayerType);
+        let mut target = entity(EnemyType);
+        assert_eq!(normal_hit_damage(&mut attacker, &mut target), 100);
+
+        // The attacker's resistance doesn't change its damage
+        attacker.resists.add("fire", 50.0);
+        assert_eq!(normal_hit_damage(&mut attacker, &mut target), 100);
+
+        target.resists.add("fire", 50.0);
+        assert_eq!(normal_hit_damage(&mut attacker, &mut target), 50);
+
+        // "all" resistance applies to every element, and gear resistance is capped
+        target.resists = Dict::from([("all", 10.0)]);
+        target.gear_resists = Dict::from([("fire", 90.0)]);
+        assert_eq!(target.get_resist("fire"), 10.0 + GEAR_RESIST_CAP);
+        assert_eq!(target.get_resist("all"), 10.0);
+        assert_eq!(normal_hit_damage(&mut attacker, &mut target), 10);
+    }
+
+    #[test]
+    fn gear_crit_is_capped() {
+        let mut entity = entity(PlayerType);
+        entity.gear_bonuses.add("crit", 150.0);
+        assert_eq!(entity.get_bonus("crit"), GEAR_CRIT_CAP);
+
+        // Crit from effects isn't capped
+        entity.bonuses.add("crit", 20.0);
+        assert_eq!(entity.get_bonus("crit"), GEAR_CRIT_CAP + 20.0);
+
+        entity.gear_bonuses.set("crit", 30.0);
+        assert_eq!(entity.get_bonus("crit"), 50.0);
+    }
+
+    #[test]
+    fn end_and_wis_scale_hp_and_mp() {
+        let mut entity = entity(PlayerType);
+        entity.recalculate_stat_bonuses(&Dict::from([("END", 10.0), ("WIS", 4.0)]));
+        assert_eq!(entity.max_hp, 1050);
+        assert_eq!(entity.max_mp, 1020);
+
+        entity.recalculate_stat_bonuses(&Dict::from([("END", -10.0), ("WIS", -4.0)]));
+        assert_eq!(entity.max_hp, 1000);
+        assert_eq!(entity.max_mp, 1000);
+    }
+
+    #[test]
+    fn effects_only_scale_hp_and_mp_with_their_own_stats() {
+        let mut entity = entity(PlayerType);
+        let stats = Dict::from([("END", 100.0), ("WIS", 100.0)]);
+        entity.recalculate_stat_bonuses(&stats);
+        entity.bonuses.merge(&stats);
+        assert_eq!(entity.max_hp, 1500);
+        assert_eq!(entity.max_mp, 1500);
+
+        // An effect that doesn't change END or WIS doesn't change the max HP or MP
+        let effect = Effect::new(
+            "Test",
+            None,
+            2,
+            Some(Dict::from([("STR", 50.0), ("END", 2.0)])),
+            None,
+            None,
+            None,
+            false,
+        );
+        entity.add_effect(effect);
+        assert_eq!(entity.max_hp, 1510);
+        assert_eq!(entity.max_mp, 1500);
+
+        entity.remove_effect(0);
+        assert_eq!(entity.max_hp, 1500);
+        assert_eq!(entity.max_mp, 1500);
+    }
+
+    #[test]
+    fn derived_bonuses_count_gear_stats() {
+        let mut entity = entity(PlayerType);
+        let gear = Dict::from([("LUK", 15.0)]);
+        entity.recalculate_stat_bonuses(&gear);
+        entity.gear_bonuses.merge(&gear);
+
+        // The floor of the derived bonus uses the stats from gear and effects together
+        let stats = Dict::from([("LUK", 5.0)]);
+        entity.recalculate_stat_bonuses(&stats);
+        entity.bonuses.merge(&stats);
+        assert_eq!(entity.get_bonus("crit"), 2.0);
+        assert_eq!(entity.get_bonus("melee_def"), 1.0);
+    }
+}
@@ -4,9 +4,13 @@ use crate::game::types::damage::{DamageRange, DamageType};
 use crate::game::types::dict::Dict;
 use crate::game::types::gear::{Item, Slot};
 use crate::game::types::skill::Skill;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+// The damage of the player without a weapon
+pub const UNARMED_DAMAGE: f32 = 20.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Player {
     pub base: Entity,
 
@@ -27,10 +31,10 @@ impl Player {
                 "Player".to_string(),
                 level,
                 PlayerType,
-                DamageRange::from(20.0),
+                DamageRange::from(UNARMED_DAMAGE),
                 DamageType::Melee,
                 elem.to_string(),
-                stats.clone(),
+                Dict::new(),
                 Dict::new(),
                 Vec::new(),
             ),
@@ -41,6 +45,7 @@ impl Player {
         };
 
         result.base.recalculate_stat_bonuses(&stats);
+        result.base.bonuses.merge(&stats);
         result.base.hp = result.base.max_hp;
         result.base.mp = result.base.max_mp;
 
@@ -50,11 +55,12 @@ impl Player {
     pub fn use_skill(&mut self, index: usize) {
         let skill = self.skills.get_mut(index).unwrap();
         skill.current_cooldown = skill.cooldown + 1;
+        skill.uses += 1;
         self.base.mp -= skill.mana;
     }
 
     pub fn update_skill_cooldowns(&mut self) {
-        for mut skill in &mut self.skills {
+        for skill in &mut self.skills {
             skill.current_cooldown = (skill.current_cooldown - 1).max(0);
         }
     }
@@ -82,19 +88,134 @@ impl Player {
         self.current_skill_index = None;
     }
 
+    // Gear is equipped before the fight, so the player stays at full HP and MP
     pub fn equip(&mut self, item: Item) {
         self.unequip(item.slot);
 
-        self.base.bonuses.merge(&item.bonuses);
-        self.base.resists.merge(&item.resists);
+        self.base.recalculate_stat_bonuses(&item.bonuses);
+        self.base.gear_bonuses.merge(&item.bonuses);
+        self.base.gear_resists.merge(&item.resists);
+
+        if let Some(dmg) = item.dmg {
+            self.base.dmg = dmg;
+        }
+        if let Some(dmg_type) = item.dmg_type {
+            self.base.dmg_type = dmg_type;
+        }
 
+        self.base.hp = self.base.max_hp;
+        self.base.mp = self.base.max_mp;
         self.items.insert(item.slot, item);
     }
 
     pub fn unequip(&mut self, slot: Slot) {
         if let Some(item) = self.items.remove(&slot) {
-            self.base.bonuses.unmerge(&item.bonuses);
-            self.base.resists.unmerge(&item.resists);
+            self.base.recalculate_stat_bonuses(&-item.bonuses.clone());
+            self.base.gear_bonuses.unmerge(&item.bonuses);
+            self.base.gear_resists.unmerge(&item.resists);
+
+            if item.dmg.is_some() {
+                self.base.dmg = DamageRange::from(UNARMED_DAMAGE);
+                self.base.dmg_type = DamageType::Melee;
+            }
+
+            self.base.hp = self.base.max_hp;
+            self.base.mp = self.base.max_mp;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: [&str; 8] = [
+        "STR",
+        "END",
+        "WIS",
+        "LUK",
+        "crit",
+        "bonus",
+        "melee_def",
+        "immobility",
+    ];
+
+    fn item(slot: Slot, dmg: Option<DamageRange>) -> Item {
+        Item {
+            slot,
+            level: 1,
+            dmg,
+            dmg_type: dmg.map(|_| DamageType::Magic),
+            bonuses: Dict::from([("END", 12.0), ("WIS", 7.0), ("LUK", 25.0), ("crit", 3.0)]),
+            resists: Dict::from([("fire", 5.0), ("all", 2.0)]),
+        }
+    }
+
+    // The values of the player that gear can change
+    fn summary(player: &Player) -> (i32, i32, DamageRange, DamageType, Vec<f32>) {
+        let base = &player.base;
+        let values = KEYS
+            .iter()
+            .map(|key| base.get_bonus(key))
+            .chain(["fire", "ice", "immobility", "health"].map(|elem| base.get_resist(elem)))
+            .collect();
+
+        (base.max_hp, base.max_mp, base.dmg, base.dmg_type, values)
+    }
+
+    #[test]
+    fn equip_unequip_round_trip() {
+        let stats = Dict::from([("END", 33.0), ("WIS", 18.0), ("LUK", 5.0)]);
+        let mut player = Player::new(stats, 10, "fire", Vec::new());
+        let before = summary(&player);
+
+        player.equip(item(Slot::Weapon, Some(DamageRange::new(10.0, 30.0))));
+        player.equip(item(Slot::Ring, None));
+        assert_eq!(player.base.max_hp, before.0 + 2 * 12 * 5);
+        assert_eq!(player.base.max_mp, before.1 + 2 * 7 * 5);
+        assert_eq!(player.base.dmg, DamageRange::new(10.0, 30.0));
+        assert_eq!(player.base.dmg_type, DamageType::Magic);
+        assert_eq!(player.base.get_resist("ice"), 4.0);
+        assert_eq!(player.base.hp, player.base.max_hp);
+
+        // Equipping an item in a used slot replaces the old one
+        player.equip(item(Slot::Ring, None));
+        assert_eq!(player.base.max_hp, before.0 + 2 * 12 * 5);
+
+        player.unequip(Slot::Ring);
+        player.unequip(Slot::Weapon);
+        assert_eq!(summary(&player), before);
+        assert_eq!(player.base.dmg, DamageRange::from(UNARMED_DAMAGE));
+        assert_eq!(player.base.hp, player.base.max_hp);
+    }
+
+    #[test]
+    fn new_counts_stats_once() {
+        let stats = Dict::from([("END", 10.0), ("LUK", 20.0)]);
+        let player = Player::new(stats, 1, "fire", Vec::new());
+        assert_eq!(player.base.max_hp, 150);
+        assert_eq!(player.base.get_bonus("LUK"), 20.0);
+        assert_eq!(player.base.get_bonus("crit"), 2.0);
+        assert_eq!(player.base.get_resist("immobility"), 2.0);
+    }
+
+    #[test]
+    fn cooldown_between_turns_is_at_most_the_skill_cooldown() {
+        let mut player = Player::new(
+            Dict::new(),
+            1,
+            "fire",
+            vec![Skill::new("Attack", 0, 0), Skill::new("Fury", 0, 3)],
+        );
+
+        player.use_skill(1);
+        player.update_skill_cooldowns();
+        assert_eq!(player.skills[1].current_cooldown, 3);
+        assert!(!player.skills[1].available());
+
+        for _ in 0..3 {
+            player.update_skill_cooldowns();
         }
+        assert!(player.skills[1].available());
     }
 }
@@ -0,0 +1,106 @@
+use crate::game::game_manager::{GameManager, GameStatus};
+
+// Simulated fights that take longer than this are stopped, and count as lost
+pub const MAX_TURNS: i32 = 100;
+
+// The history of a single fight, recorded after every turn
+#[derive(Debug, Clone)]
+pub struct FightRecord {
+    pub won: bool,
+    pub turns: i32,
+
+    // Total damage dealt to the enemies
+    pub damage: f64,
+
+    // The player's MP at the start of the fight and after every turn
+    pub mp: Vec<i32>,
+}
+
+impl FightRecord {
+    pub fn new(game: &GameManager) -> Self {
+        let mut result = Self {
+            won: false,
+            turns: 0,
+            damage: 0.0,
+            mp: Vec::new(),
+        };
+        result.record_turn(game);
+
+        result
+    }
+
+    pub fn record_turn(&mut self, game: &GameManager) {
+        self.won = matches!(game.get_status(), GameStatus::Won);
+        self.turns = game.turn - 1;
+        self.damage = game
+            .enemies
+            .iter()
+            .map(|enemy| {
+                let entity = enemy.get_base_entity();
+                (entity.max_hp - entity.hp.max(0)) as f64
+            })
+            .sum();
+        self.mp.push(game.player.get_base_entity().mp);
+    }
+
+    pub fn damage_per_turn(&self) -> f64 {
+        self.damage / self.turns.max(1) as f64
+    }
+}
+
+// Averages over several fights
+#[derive(Debug, Clone)]
+pub struct FightSummary {
+    pub fights: usize,
+    pub win_rate: f64,
+
+    // Only fights that were won are counted
+    pub turns_to_kill: Option<f64>,
+
+    pub damage_per_turn: f64,
+
+    // The average MP after every turn, of the fights that lasted that long
+    pub mana_curve: Vec<f64>,
+}
+
+impl From<&[FightRecord]> for FightSummary {
+    fn from(records: &[FightRecord]) -> Self {
+        let fights = records.len();
+        let won_records: Vec<&FightRecord> = records.iter().filter(|record| record.won).collect();
+
+        let turns_to_kill = if won_records.is_empty() {
+            None
+        } else {
+            Some(
+                won_records
+                    .iter()
+                    .map(|record| record.turns as f64)
+                    .sum::<f64>()
+                    / won_records.len() as f64,
+            )
+        };
+
+        let longest_fight = records.iter().map(|record| record.mp.len()).max();
+        let mana_curve = (0..longest_fight.unwrap_or(0))
+            .map(|turn| {
+                let mp: Vec<i32> = records
+                    .iter()
+                    .filter_map(|record| record.mp.get(turn).copied())
+                    .collect();
+                mp.iter().sum::<i32>() as f64 / mp.len() as f64
+            })
+            .collect();
+
+        Self {
+            fights,
+            win_rate: won_records.len() as f64 / fights.max(1) as f64,
+            turns_to_kill,
+            damage_per_turn: records
+                .iter()
+                .map(|record| record.damage_per_turn())
+                .sum::<f64>()
+                / fights.max(1) as f64,
+            mana_curve,
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::game::entities::entity::EntityTrait;
+use crate::game::entities::entity::{Entity, EntitySnapshot, EntityTrait};
+use serde::{Deserialize, Serialize};
 
 pub enum GameStatus {
     Won,
@@ -13,6 +14,28 @@ pub struct GameManager {
     pub turn: i32,
 }
 
+// A serializable copy of a game, used to send games to other threads and workers
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameSnapshot {
+    player: EntitySnapshot,
+    enemies: Vec<EntitySnapshot>,
+    turn: i32,
+}
+
+impl From<GameSnapshot> for GameManager {
+    fn from(snapshot: GameSnapshot) -> Self {
+        GameManager {
+            player: snapshot.player.restore(),
+            enemies: snapshot
+                .enemies
+                .into_iter()
+                .map(|enemy| enemy.restore())
+                .collect(),
+            turn: snapshot.turn,
+        }
+    }
+}
+
 impl GameManager {
     pub fn new(
         player: Box<dyn EntityTrait + Send>,
@@ -61,6 +84,43 @@ impl GameManager {
         }
     }
 
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            player: self.player.snapshot(),
+            enemies: self.enemies.iter().map(|enemy| enemy.snapshot()).collect(),
+            turn: self.turn,
+        }
+    }
+
+    // The player is at index 0, followed by the enemies
+    pub fn get_entity_trait(&self, index: usize) -> &(dyn EntityTrait + Send) {
+        if index == 0 {
+            self.player.as_ref()
+        } else {
+            self.enemies[index - 1].as_ref()
+        }
+    }
+
+    pub fn get_entity_trait_mut(&mut self, index: usize) -> &mut (dyn EntityTrait + Send) {
+        if index == 0 {
+            self.player.as_mut()
+        } else {
+            self.enemies[index - 1].as_mut()
+        }
+    }
+
+    pub fn get_entity(&self, index: usize) -> &Entity {
+        self.get_entity_trait(index).get_base_entity()
+    }
+
+    pub fn get_entity_mut(&mut self, index: usize) -> &mut Entity {
+        self.get_entity_trait_mut(index).get_base_entity_mut()
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.enemies.len() + 1
+    }
+
     pub fn get_status(&self) -> GameStatus {
         if self.player.get_base_entity().hp <= 0 {
             return GameStatus::Lost;
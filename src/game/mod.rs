@@ -1,4 +1,6 @@
 pub mod entities;
+pub mod fight_record;
 pub mod game_manager;
+pub mod rotation;
 pub mod types;
 mod utils;
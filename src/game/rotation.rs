@@ -0,0 +1,265 @@
+use crate::game::fight_record::FightRecord;
+use crate::game::game_manager::{GameManager, GameStatus};
+use crate::game::types::skill::Skill;
+use std::fmt::{Display, Formatter};
+
+// Separates the steps of a rotation, e.g. `Target > Locker > Attack x2`
+const STEP_SEPARATOR: char = '>';
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    EmptyStep { step: usize },
+    UnknownSkill { step: usize, name: String },
+    InvalidRepeat { step: usize, count: String },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::EmptyStep { step } => write!(f, "Step {} is empty", step),
+            ParseError::UnknownSkill { step, name } => {
+                write!(f, "Step {}: unknown skill \"{}\"", step, name)
+            }
+            ParseError::InvalidRepeat { step, count } => {
+                write!(f, "Step {}: invalid repeat count \"{}\"", step, count)
+            }
+        }
+    }
+}
+
+// Why a step of a rotation couldn't be used
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepError {
+    OnCooldown { turns_left: i32 },
+    NotEnoughMana { needed: i32, available: i32 },
+    GameOver,
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepError::OnCooldown { turns_left } => {
+                write!(f, "On cooldown for {} more turns", turns_left)
+            }
+            StepError::NotEnoughMana { needed, available } => {
+                write!(f, "Needs {} MP, but only {} MP left", needed, available)
+            }
+            StepError::GameOver => write!(f, "The fight is already over"),
+        }
+    }
+}
+
+pub struct SimulatedStep {
+    pub skill: usize,
+
+    // The turn the skill was used on, or would have been used on
+    pub turn: i32,
+
+    // Illegal steps are skipped, and don't use a turn
+    pub result: Result<(), StepError>,
+
+    // The game after the step
+    pub game: GameManager,
+}
+
+// A fixed sequence of skills, referred to by their index in the player's skills
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rotation {
+    pub skills: Vec<usize>,
+}
+
+impl Rotation {
+    // Parses a rotation such as `Target > Locker > Fury > Attack x2 > Curse`.
+    // Skill names are case insensitive.
+    pub fn parse(text: &str, skills: &[Skill]) -> Result<Self, ParseError> {
+        let mut result = Vec::new();
+
+        for (index, raw_step) in text.split(STEP_SEPARATOR).enumerate() {
+            let step = index + 1;
+            let mut name = raw_step.trim();
+            let mut repeat = 1;
+
+            if name.is_empty() {
+                return Err(ParseError::EmptyStep { step });
+            }
+
+            // A trailing `xN` repeats the skill N times
+            if let Some((skill_name, count)) = name.rsplit_once(char::is_whitespace) {
+                if let Some(count) = count.strip_prefix(['x', 'X']) {
+                    repeat = match count.parse() {
+                        Ok(count) if count > 0 => count,
+                        _ => {
+                            return Err(ParseError::InvalidRepeat {
+                                step,
+                                count: count.to_string(),
+                            })
+                        }
+                    };
+                    name = skill_name.trim_end();
+                }
+            }
+
+            let skill = skills
+                .iter()
+                .position(|skill| skill.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| ParseError::UnknownSkill {
+                    step,
+                    name: name.to_string(),
+                })?;
+
+            result.resize(result.len() + repeat, skill);
+        }
+
+        Ok(Self { skills: result })
+    }
+
+    // Checks whether `skill` can be used in `game`
+    pub fn check_step(game: &GameManager, skill: usize) -> Result<(), StepError> {
+        if !matches!(game.get_status(), GameStatus::InProgress) {
+            return Err(StepError::GameOver);
+        }
+
+        let player_ref = game.player.get_base_type();
+        let player = player_ref.as_player();
+        if player.get_available_skills().contains(&skill) {
+            return Ok(());
+        }
+
+        let skill = &player.skills[skill];
+        if !skill.available() {
+            Err(StepError::OnCooldown {
+                turns_left: skill.current_cooldown,
+            })
+        } else {
+            Err(StepError::NotEnoughMana {
+                needed: skill.mana,
+                available: player.base.mp,
+            })
+        }
+    }
+
+    // Plays the rotation in `game`, one turn per legal step
+    pub fn simulate(&self, mut game: GameManager) -> Vec<SimulatedStep> {
+        let mut steps = Vec::new();
+
+        for &skill in &self.skills {
+            let turn = game.turn;
+            let result = Self::check_step(&game, skill);
+
+            if result.is_ok() {
+                game.player
+                    .get_base_type_mut()
+                    .as_player()
+                    .set_current_skill(skill);
+                game.do_turn();
+            }
+
+            steps.push(SimulatedStep {
+                skill,
+                turn,
+                result,
+                game: game.clone(),
+            });
+        }
+
+        steps
+    }
+
+    // Repeats the rotation until the fight is over, or `max_turns` turns were played.
+    // Illegal steps are skipped, and the fight stops if a whole repetition was illegal.
+    pub fn play_fight(&self, mut game: GameManager, max_turns: i32) -> FightRecord {
+        let mut record = FightRecord::new(&game);
+
+        while matches!(game.get_status(), GameStatus::InProgress) && record.turns < max_turns {
+            let turns = record.turns;
+
+            for &skill in &self.skills {
+                if record.turns >= max_turns || Self::check_step(&game, skill).is_err() {
+                    continue;
+                }
+
+                game.player
+                    .get_base_type_mut()
+                    .as_player()
+                    .set_current_skill(skill);
+                game.do_turn();
+                record.record_turn(&game);
+            }
+
+            if record.turns == turns {
+                break;
+            }
+        }
+
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skills() -> Vec<Skill> {
+        vec![
+            Skill::new("Attack", 0, 0),
+            Skill::new("Fury", 40, 2),
+            Skill::new("Curse", 20, 3),
+        ]
+    }
+
+    fn parse(text: &str) -> Result<Vec<usize>, ParseError> {
+        Rotation::parse(text, &skills()).map(|rotation| rotation.skills)
+    }
+
+    #[test]
+    fn repeats_and_names() {
+        assert_eq!(
+            parse("Attack x2 > fury > CURSE X3"),
+            Ok(vec![0, 0, 1, 2, 2, 2])
+        );
+        assert_eq!(parse("  Fury  "), Ok(vec![1]));
+
+        assert_eq!(
+            parse("Fury > Attack x0"),
+            Err(ParseError::InvalidRepeat {
+                step: 2,
+                count: "0".to_string()
+            })
+        );
+        assert_eq!(
+            parse("Attack xy"),
+            Err(ParseError::InvalidRepeat {
+                step: 1,
+                count: "y".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_skills_are_rejected() {
+        assert_eq!(
+            parse("Attack > Heal x2"),
+            Err(ParseError::UnknownSkill {
+                step: 2,
+                name: "Heal".to_string()
+            })
+        );
+        assert_eq!(
+            parse("x2"),
+            Err(ParseError::UnknownSkill {
+                step: 1,
+                name: "x2".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn empty_steps_are_rejected() {
+        assert_eq!(parse(""), Err(ParseError::EmptyStep { step: 1 }));
+        assert_eq!(
+            parse("Attack >> Fury"),
+            Err(ParseError::EmptyStep { step: 2 })
+        );
+        assert_eq!(parse("Attack > "), Err(ParseError::EmptyStep { step: 2 }));
+    }
+}
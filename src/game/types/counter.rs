@@ -0,0 +1,22 @@
+// Armor-specific state that isn't part of `Entity`, such as Pirate's plunder stacks
+#[derive(Debug, Clone)]
+pub struct Counter {
+    pub name: String,
+    pub value: i32,
+    pub max: i32,
+}
+
+impl Counter {
+    pub fn new(name: &str, value: i32, max: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            max,
+        }
+    }
+
+    // Counters with a max of 1 are on/off flags
+    pub fn is_flag(&self) -> bool {
+        self.max == 1
+    }
+}
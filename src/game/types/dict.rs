@@ -1,5 +1,5 @@
 use crate::game::types::damage::DamageType;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::hash_map::{Iter, Keys};
 use std::collections::HashMap;
 use std::ops::Neg;
@@ -73,7 +73,7 @@ impl Dict {
         }
     }
 
-    pub fn iter(&self) -> Iter<String, f32> {
+    pub fn iter(&self) -> Iter<'_, String, f32> {
         self.map.iter()
     }
 
@@ -129,6 +129,15 @@ impl Neg for Dict {
     }
 }
 
+impl Serialize for Dict {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.map.serialize(serializer)
+    }
+}
+
 impl<'de> Deserialize<'de> for Dict {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
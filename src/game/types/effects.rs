@@ -1,14 +1,15 @@
 use crate::game::types::damage::DamageRange;
 use crate::game::types::dict::Dict;
+use serde::{Deserialize, Serialize};
 use std::ops::Div;
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Stun {
     Normal,
     Automatic,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DoT {
     pub dmg_range: DamageRange,
     pub elem: String,
@@ -34,7 +35,7 @@ impl Div<f32> for DoT {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Effect {
     pub name: String,
     pub duration: i8,
@@ -1,8 +1,9 @@
 use crate::game::types::damage::{DamageRange, DamageType};
 use crate::game::types::dict::Dict;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 
-#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Slot {
     Weapon,
     Helm,
@@ -27,22 +28,25 @@ impl Slot {
     }
 }
 
-impl ToString for Slot {
-    fn to_string(&self) -> String {
-        match self {
-            Slot::Weapon => "Weapon",
-            Slot::Helm => "Helm",
-            Slot::Cape => "Cape",
-            Slot::Necklace => "Necklace",
-            Slot::Belt => "Belt",
-            Slot::Ring => "Ring",
-            Slot::Bracer => "Bracer",
-        }
-        .to_string()
+impl Display for Slot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Slot::Weapon => "Weapon",
+                Slot::Helm => "Helm",
+                Slot::Cape => "Cape",
+                Slot::Necklace => "Necklace",
+                Slot::Belt => "Belt",
+                Slot::Ring => "Ring",
+                Slot::Bracer => "Bracer",
+            }
+        )
     }
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Item {
     pub slot: Slot,
     pub level: u8,
@@ -1,6 +1,7 @@
 use crate::game::types::damage::{DamageRange, DamageType};
 use crate::game::types::dict::Dict;
 use crate::game::types::effects::Effect;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone)]
 pub struct Hit {
@@ -15,3 +16,46 @@ pub struct Hit {
     // List of effects to apply after calculating the damage
     pub after_hit_effects: Option<Vec<Effect>>,
 }
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HitOutcome {
+    Miss,
+    Glance,
+    Normal,
+    Crit,
+}
+
+impl HitOutcome {
+    pub fn vec() -> Vec<HitOutcome> {
+        vec![
+            HitOutcome::Miss,
+            HitOutcome::Glance,
+            HitOutcome::Normal,
+            HitOutcome::Crit,
+        ]
+    }
+}
+
+impl Display for HitOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HitOutcome::Miss => "Miss",
+                HitOutcome::Glance => "Glance",
+                HitOutcome::Normal => "Normal",
+                HitOutcome::Crit => "Crit",
+            }
+        )
+    }
+}
+
+// A hit outcome chosen by the user instead of rolled, to mirror a real fight
+#[derive(Debug, Copy, Clone)]
+pub struct ForcedHit {
+    pub outcome: HitOutcome,
+
+    // Replaces the calculated damage
+    pub damage: Option<i32>,
+}
@@ -1,3 +1,4 @@
+pub mod counter;
 pub mod damage;
 pub mod dict;
 pub mod effects;
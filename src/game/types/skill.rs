@@ -1,10 +1,15 @@
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Skill {
     pub name: String,
     pub mana: i32,
     pub cooldown: i32,
 
     pub current_cooldown: i32,
+
+    // Number of times the skill was used this fight
+    pub uses: i32,
 }
 
 impl Skill {
@@ -14,6 +19,7 @@ impl Skill {
             mana,
             cooldown,
             current_cooldown: 0,
+            uses: 0,
         }
     }
 
@@ -1,9 +1,16 @@
+use crate::ai::rotation_cache::{RotationCache, SharedRotationCache};
 use crate::game::types::gear::Slot;
 use crate::gui::gear::GuiItem;
 use crate::gui::options::{AIOptions, GameOptions, GearOptions};
+use crate::gui::saved_state::SavedState;
+#[cfg(target_arch = "wasm32")]
+use crate::gui::url_state::UrlState;
 use crate::gui::views::ai_view::AIView;
+use crate::gui::views::compare_view::CompareView;
 use crate::gui::views::gear_view::GearView;
 use crate::gui::views::interactive_view::InteractiveView;
+use crate::gui::views::optimizer_view::OptimizerView;
+use crate::gui::views::rotation_view::RotationView;
 use crate::gui::views::side_panel_view::SidePanelView;
 use eframe::Frame;
 use egui::{Color32, Context, FontFamily, FontId, TextStyle, Ui};
@@ -11,32 +18,49 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub enum AppView {
+    #[default]
     AI,
     Interactive,
+    Rotation,
+    Compare,
     Gear,
-}
-
-impl Default for AppView {
-    fn default() -> Self {
-        Self::AI
-    }
+    Optimizer,
 }
 
 pub struct App {
     view: AppView,
     ai_view: AIView,
     interactive_view: InteractiveView,
+    rotation_view: RotationView,
+    compare_view: CompareView,
     gear_view: GearView,
+    optimizer_view: OptimizerView,
 
     side_panel: SidePanelView,
+
+    game_options: Rc<RefCell<GameOptions>>,
+    ai_options: Rc<RefCell<AIOptions>>,
+    gear_options: Rc<RefCell<GearOptions>>,
+    rotation_cache: SharedRotationCache,
+
+    // The last state written to the page's URL
+    #[cfg(target_arch = "wasm32")]
+    url_fragment: String,
+
+    // Why the options of the page's URL were ignored
+    #[cfg(target_arch = "wasm32")]
+    url_error: Option<String>,
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
         self.configure_panels();
 
+        #[cfg(target_arch = "wasm32")]
+        self.update_url();
+
         egui::SidePanel::right("configuration_panel")
             .resizable(false)
             .show(ctx, |ui| {
@@ -47,6 +71,21 @@ impl eframe::App for App {
             self.draw_central_panel(ui);
         });
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        SavedState::new(
+            &self.game_options.borrow(),
+            &self.ai_options.borrow(),
+            &self.gear_options.borrow(),
+        )
+        .save(storage);
+
+        // The native cache has its own file, since it can get much bigger than the rest of the state
+        #[cfg(not(target_arch = "wasm32"))]
+        self.rotation_cache.borrow_mut().save();
+        #[cfg(target_arch = "wasm32")]
+        self.rotation_cache.borrow_mut().save(storage);
+    }
 }
 
 impl App {
@@ -72,16 +111,84 @@ impl App {
         style.visuals.override_text_color = Some(Color32::WHITE);
         cc.egui_ctx.set_style(style);
 
-        let game_options = Rc::new(RefCell::new(GameOptions::default()));
-        let ai_options = Rc::new(RefCell::new(AIOptions::default()));
-        let gear_options = Rc::new(RefCell::new(GearOptions::default()));
+        let gear = Self::get_gear();
+        let options = SavedState::load(cc.storage)
+            .map(|state| state.restore(&gear))
+            .unwrap_or_default();
+
+        // A shared link overrides the saved options
+        #[cfg(target_arch = "wasm32")]
+        let mut url_error = None;
+        #[cfg(target_arch = "wasm32")]
+        let options = match UrlState::read() {
+            Some(state) => match state.validate() {
+                Ok(()) => state.restore(&gear, options),
+                Err(error) => {
+                    url_error = Some(format!("The link's options were ignored: {}", error));
+                    options
+                }
+            },
+            None => options,
+        };
+
+        let (game_options, ai_options, gear_options) = options;
+        let game_options = Rc::new(RefCell::new(game_options));
+        let ai_options = Rc::new(RefCell::new(ai_options));
+        let gear_options = Rc::new(RefCell::new(gear_options));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let rotation_cache = Rc::new(RefCell::new(RotationCache::load()));
+        #[cfg(target_arch = "wasm32")]
+        let rotation_cache = Rc::new(RefCell::new(RotationCache::load(cc.storage)));
 
         Self {
             view: AppView::default(),
-            ai_view: AIView::new(game_options.clone(), ai_options.clone()),
-            interactive_view: InteractiveView::new(game_options.clone()),
-            gear_view: GearView::new(Self::get_gear(), gear_options.clone()),
-            side_panel: SidePanelView::new(game_options, ai_options, gear_options),
+            ai_view: AIView::new(
+                game_options.clone(),
+                ai_options.clone(),
+                rotation_cache.clone(),
+            ),
+            interactive_view: InteractiveView::new(
+                game_options.clone(),
+                ai_options.clone(),
+                rotation_cache.clone(),
+            ),
+            rotation_view: RotationView::new(game_options.clone()),
+            compare_view: CompareView::new(game_options.clone(), ai_options.clone()),
+            optimizer_view: OptimizerView::new(
+                gear.clone(),
+                game_options.clone(),
+                gear_options.clone(),
+            ),
+            gear_view: GearView::new(gear, game_options.clone(), gear_options.clone()),
+            side_panel: SidePanelView::new(
+                game_options.clone(),
+                ai_options.clone(),
+                gear_options.clone(),
+            ),
+            game_options,
+            ai_options,
+            gear_options,
+            rotation_cache,
+            #[cfg(target_arch = "wasm32")]
+            url_fragment: String::new(),
+            #[cfg(target_arch = "wasm32")]
+            url_error,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn update_url(&mut self) {
+        let fragment = UrlState::new(
+            &self.game_options.borrow(),
+            &self.ai_options.borrow(),
+            &self.gear_options.borrow(),
+        )
+        .encode();
+
+        if fragment != self.url_fragment {
+            UrlState::write(&fragment);
+            self.url_fragment = fragment;
         }
     }
 
@@ -104,20 +211,54 @@ impl App {
             {
                 self.view = AppView::Interactive;
             }
+            if ui
+                .selectable_label(matches!(self.view, AppView::Rotation), "Rotation")
+                .clicked()
+            {
+                self.view = AppView::Rotation;
+            }
+            if ui
+                .selectable_label(matches!(self.view, AppView::Compare), "Compare")
+                .clicked()
+            {
+                self.view = AppView::Compare;
+            }
             if ui
                 .selectable_label(matches!(self.view, AppView::Gear), "Gear")
                 .clicked()
             {
                 self.view = AppView::Gear;
             }
+            if ui
+                .selectable_label(matches!(self.view, AppView::Optimizer), "Optimizer")
+                .clicked()
+            {
+                self.view = AppView::Optimizer;
+            }
         });
 
         ui.separator();
 
+        #[cfg(target_arch = "wasm32")]
+        if let Some(error) = &self.url_error {
+            let mut dismissed = false;
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::RED, error);
+                dismissed = ui.button("ｘ").clicked();
+            });
+            if dismissed {
+                self.url_error = None;
+            }
+            ui.separator();
+        }
+
         match self.view {
             AppView::AI => self.ai_view.draw(ui),
             AppView::Interactive => self.interactive_view.draw(ui),
+            AppView::Rotation => self.rotation_view.draw(ui),
+            AppView::Compare => self.compare_view.draw(ui),
             AppView::Gear => self.gear_view.draw(ui),
+            AppView::Optimizer => self.optimizer_view.draw(ui),
         };
     }
 
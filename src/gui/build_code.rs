@@ -0,0 +1,322 @@
+use crate::game::entities::armors::Armor;
+use crate::game::types::gear::Slot;
+use crate::gui::gear::{GearSet, GuiItem};
+use crate::gui::options::{GameOptions, MAX_LEVEL};
+use crate::gui::saved_state::SavedGearSet;
+use crate::gui::stats_handler::{StatsError, StatsHandler, STATS};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+
+// Changing the format should bump this, while still reading the older versions
+const BUILD_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+    InvalidCode,
+    InvalidJson(String),
+    UnsupportedVersion(u64),
+    InvalidLevel(i32),
+    UnknownStat(String),
+    InvalidStats(StatsError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::InvalidCode => write!(f, "Not a valid build code"),
+            ImportError::InvalidJson(error) => write!(f, "Invalid build: {}", error),
+            ImportError::UnsupportedVersion(version) => write!(
+                f,
+                "The build was made by a newer version (format {})",
+                version
+            ),
+            ImportError::InvalidLevel(level) => {
+                write!(f, "Level {} is not between 1 and {}", level, MAX_LEVEL)
+            }
+            ImportError::UnknownStat(stat) => write!(f, "Unknown stat \"{}\"", stat),
+            ImportError::InvalidStats(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+// An item of an imported build that couldn't be equipped
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemProblem {
+    Unknown {
+        slot: Slot,
+        pedia_page_id: u32,
+    },
+    WrongSlot {
+        slot: Slot,
+        item: String,
+        item_slot: Slot,
+    },
+}
+
+impl Display for ItemProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemProblem::Unknown {
+                slot,
+                pedia_page_id,
+            } => write!(
+                f,
+                "{}: no item with pedia page {} in the database",
+                slot, pedia_page_id
+            ),
+            ItemProblem::WrongSlot {
+                slot,
+                item,
+                item_slot,
+            } => write!(f, "{}: {} is a {}", slot, item, item_slot),
+        }
+    }
+}
+
+// A gear set with the stats, level and armor it's used with, shared as JSON or as a code
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Build {
+    pub version: u64,
+    pub gear_set: SavedGearSet,
+    pub level: i32,
+    pub armor: Armor,
+    pub stats: BTreeMap<String, u32>,
+}
+
+impl Build {
+    pub fn new(gear_set: &GearSet, game_options: &GameOptions) -> Self {
+        Self {
+            version: BUILD_VERSION,
+            gear_set: SavedGearSet::from(gear_set),
+            level: game_options.level,
+            armor: game_options.armor,
+            stats: STATS
+                .iter()
+                .map(|stat| (stat.to_string(), game_options.stats.get(stat)))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    // The compact JSON in URL safe base64, so the code can be pasted anywhere
+    pub fn to_code(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_string(self).unwrap())
+    }
+
+    // Reads a build exported with `to_json` or `to_code`
+    pub fn parse(text: &str) -> Result<Self, ImportError> {
+        let text = text.trim();
+        let json = if text.starts_with('{') {
+            text.to_string()
+        } else {
+            let bytes = URL_SAFE_NO_PAD
+                .decode(text)
+                .map_err(|_| ImportError::InvalidCode)?;
+            String::from_utf8(bytes).map_err(|_| ImportError::InvalidCode)?
+        };
+
+        let build: Build = serde_json::from_str(&json)
+            .map_err(|error| ImportError::InvalidJson(error.to_string()))?;
+        build.validate()?;
+
+        Ok(build)
+    }
+
+    fn validate(&self) -> Result<(), ImportError> {
+        if self.version > BUILD_VERSION {
+            return Err(ImportError::UnsupportedVersion(self.version));
+        }
+        if !(1..=MAX_LEVEL).contains(&self.level) {
+            return Err(ImportError::InvalidLevel(self.level));
+        }
+
+        let mut stats = StatsHandler::new();
+        for (stat, value) in &self.stats {
+            if !STATS.contains(&stat.as_str()) {
+                return Err(ImportError::UnknownStat(stat.clone()));
+            }
+            stats.set(stat, *value);
+        }
+        stats
+            .validate(self.level)
+            .map_err(ImportError::InvalidStats)?;
+
+        Ok(())
+    }
+
+    // Finds the build's items in the database, items that can't be found are left unequipped
+    pub fn restore_gear_set(
+        &self,
+        gear: &HashMap<Slot, Vec<GuiItem>>,
+    ) -> (GearSet, Vec<ItemProblem>) {
+        let gear_set = self.gear_set.restore(gear);
+
+        let mut problems = Vec::new();
+        for (slot, pedia_page_id) in &self.gear_set.items {
+            if gear_set.set.contains_key(slot) {
+                continue;
+            }
+
+            let item = gear
+                .values()
+                .flatten()
+                .find(|item| item.pedia_page_id == *pedia_page_id);
+            problems.push(match item {
+                Some(item) => ItemProblem::WrongSlot {
+                    slot: *slot,
+                    item: item.name.clone(),
+                    item_slot: item.base.slot,
+                },
+                None => ItemProblem::Unknown {
+                    slot: *slot,
+                    pedia_page_id: *pedia_page_id,
+                },
+            });
+        }
+
+        (gear_set, problems)
+    }
+
+    // Stats missing from the build are set to 0
+    pub fn apply_options(&self, game_options: &mut GameOptions) {
+        game_options.level = self.level;
+        game_options.armor = self.armor;
+        for (stat, value) in &mut game_options.stats.map {
+            *value = self.stats.get(stat).copied().unwrap_or(0).to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::types::dict::Dict;
+    use crate::game::types::gear::Item;
+    use crate::gui::gear::ItemTags;
+
+    // One item per slot, with the pedia pages 1 to 7
+    fn gear() -> HashMap<Slot, Vec<GuiItem>> {
+        Slot::vec()
+            .into_iter()
+            .zip(1..)
+            .map(|(slot, pedia_page_id)| {
+                let item = GuiItem {
+                    base: Item {
+                        slot,
+                        level: 1,
+                        dmg: None,
+                        dmg_type: None,
+                        bonuses: Dict::new(),
+                        resists: Dict::new(),
+                    },
+                    name: format!("{} {}", slot, pedia_page_id),
+                    pedia_page_id,
+                    weapon_type: None,
+                    tags: ItemTags::default(),
+                };
+                (slot, vec![item])
+            })
+            .collect()
+    }
+
+    fn item(gear: &HashMap<Slot, Vec<GuiItem>>, slot: Slot) -> GuiItem {
+        gear[&slot][0].clone()
+    }
+
+    fn build(gear: &HashMap<Slot, Vec<GuiItem>>) -> (Build, GameOptions) {
+        let mut gear_set = GearSet::new("Test".to_string());
+        for slot in [Slot::Weapon, Slot::Ring] {
+            gear_set.set.insert(slot, item(gear, slot));
+        }
+
+        let mut game_options = GameOptions {
+            level: 80,
+            ..Default::default()
+        };
+        game_options.stats.set("STR", 50);
+        game_options.stats.set("WIS", 20);
+
+        (Build::new(&gear_set, &game_options), game_options)
+    }
+
+    #[test]
+    fn code_round_trip() {
+        let gear = gear();
+        let (build, game_options) = build(&gear);
+
+        for text in [build.to_code(), build.to_json()] {
+            let parsed = Build::parse(&text).unwrap();
+            assert_eq!(parsed.gear_set, build.gear_set);
+            assert_eq!(parsed.stats, build.stats);
+
+            let (gear_set, problems) = parsed.restore_gear_set(&gear);
+            assert!(problems.is_empty());
+            assert_eq!(SavedGearSet::from(&gear_set), build.gear_set);
+
+            let mut restored_options = GameOptions::default();
+            parsed.apply_options(&mut restored_options);
+            assert!(restored_options == game_options);
+        }
+    }
+
+    #[test]
+    fn invalid_codes_are_rejected() {
+        assert_eq!(
+            Build::parse("not a build code!").err(),
+            Some(ImportError::InvalidCode)
+        );
+        assert!(matches!(
+            Build::parse(&URL_SAFE_NO_PAD.encode("[1, 2]")),
+            Err(ImportError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let (mut build, _) = build(&gear());
+        build.version = BUILD_VERSION + 1;
+
+        assert_eq!(
+            Build::parse(&build.to_code()).err(),
+            Some(ImportError::UnsupportedVersion(BUILD_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn missing_items_are_reported() {
+        let gear = gear();
+        let (mut build, _) = build(&gear);
+        build.gear_set.items.insert(Slot::Helm, u32::MAX);
+        build
+            .gear_set
+            .items
+            .insert(Slot::Cape, item(&gear, Slot::Belt).pedia_page_id);
+
+        let (gear_set, mut problems) = Build::parse(&build.to_code())
+            .unwrap()
+            .restore_gear_set(&gear);
+        problems.sort_by_key(|problem| problem.to_string());
+
+        assert_eq!(gear_set.set.len(), 2);
+        assert_eq!(
+            problems,
+            vec![
+                ItemProblem::WrongSlot {
+                    slot: Slot::Cape,
+                    item: item(&gear, Slot::Belt).name,
+                    item_slot: Slot::Belt,
+                },
+                ItemProblem::Unknown {
+                    slot: Slot::Helm,
+                    pedia_page_id: u32::MAX,
+                },
+            ]
+        );
+    }
+}
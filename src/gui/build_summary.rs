@@ -0,0 +1,157 @@
+use crate::game::entities::entity::{Entity, GEAR_RESIST_CAP};
+use crate::gui::gear::GearSet;
+use crate::gui::options::GameOptions;
+use crate::gui::utils;
+use egui::{Color32, Ui};
+use std::collections::BTreeSet;
+
+// A bonus or resist of the build
+pub struct SummaryRow {
+    pub name: String,
+
+    // The total of the equipped items, before the cap
+    pub gear: f32,
+    pub cap: Option<f32>,
+
+    // The value given by stats, level and armor
+    pub stats: f32,
+    pub total: f32,
+}
+
+impl SummaryRow {
+    // Gear points above the cap, which don't do anything
+    pub fn wasted(&self) -> f32 {
+        self.cap.map_or(0.0, |cap| (self.gear - cap).max(0.0))
+    }
+}
+
+// The totals of the player with a gear set equipped
+pub struct BuildSummary {
+    pub max_hp: i32,
+    pub max_mp: i32,
+    pub min_dmg: f32,
+    pub max_dmg: f32,
+
+    pub bonuses: Vec<SummaryRow>,
+    pub resists: Vec<SummaryRow>,
+}
+
+impl BuildSummary {
+    pub fn new(game_options: &GameOptions, gear_set: &GearSet) -> Self {
+        let player = game_options.create_player(Some(gear_set));
+        let player = player.get_base_entity();
+
+        let bonus_keys: BTreeSet<&String> = player
+            .bonuses
+            .keys()
+            .chain(player.gear_bonuses.keys())
+            .collect();
+        let bonuses = bonus_keys
+            .into_iter()
+            .map(|key| SummaryRow {
+                name: key.clone(),
+                gear: player.gear_bonuses.get(key),
+                cap: Entity::get_gear_bonus_cap(key),
+                stats: player.bonuses.get(key),
+                total: player.get_bonus(key),
+            })
+            .filter(|row| row.gear != 0.0 || row.total != 0.0)
+            .collect();
+
+        let resist_keys: BTreeSet<&String> = player
+            .resists
+            .keys()
+            .chain(player.gear_resists.keys())
+            .collect();
+        let resists = resist_keys
+            .into_iter()
+            .map(|key| SummaryRow {
+                name: key.clone(),
+                gear: player.get_gear_resist(key),
+                cap: Some(GEAR_RESIST_CAP),
+                stats: player.get_resist(key) - player.get_gear_resist(key).min(GEAR_RESIST_CAP),
+                total: player.get_resist(key),
+            })
+            .filter(|row| row.gear != 0.0 || row.total != 0.0)
+            .collect();
+
+        Self {
+            max_hp: player.max_hp,
+            max_mp: player.max_mp,
+            min_dmg: player.dmg.min,
+            max_dmg: player.dmg.max,
+            bonuses,
+            resists,
+        }
+    }
+
+    pub fn draw(&self, ui: &mut Ui) {
+        egui::Grid::new("build_summary_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("HP:");
+                ui.label(self.max_hp.to_string());
+                ui.end_row();
+
+                ui.label("MP:");
+                ui.label(self.max_mp.to_string());
+                ui.end_row();
+
+                ui.label("Damage:");
+                ui.label(format!("{}-{}", self.min_dmg, self.max_dmg));
+                ui.end_row();
+            });
+
+        ui.separator();
+        Self::draw_rows(ui, "build_summary_bonuses_grid", "Bonuses", &self.bonuses);
+        ui.separator();
+        Self::draw_rows(ui, "build_summary_resists_grid", "Resists", &self.resists);
+
+        let wasted: Vec<&SummaryRow> = self
+            .bonuses
+            .iter()
+            .chain(&self.resists)
+            .filter(|row| row.wasted() > 0.0)
+            .collect();
+        if !wasted.is_empty() {
+            ui.separator();
+            for row in wasted {
+                ui.colored_label(
+                    Color32::RED,
+                    format!(
+                        "{} gear {} over the cap of {}",
+                        row.wasted(),
+                        utils::to_title_case(&row.name),
+                        row.cap.unwrap()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn draw_rows(ui: &mut Ui, id: &str, label: &str, rows: &[SummaryRow]) {
+        egui::Grid::new(id)
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(label);
+                ui.label("Gear");
+                ui.label("Stats");
+                ui.label("Total");
+                ui.end_row();
+
+                for row in rows {
+                    ui.label(format!("{}:", utils::to_title_case(&row.name)));
+                    if row.wasted() > 0.0 {
+                        ui.colored_label(Color32::RED, row.gear.to_string())
+                            .on_hover_text(format!("Capped at {}", row.cap.unwrap()));
+                    } else {
+                        ui.label(row.gear.to_string());
+                    }
+                    ui.label(row.stats.to_string());
+                    ui.label(row.total.to_string());
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -1,8 +1,10 @@
 use crate::game::types::gear::{Item, Slot};
-use crate::gui::widgets::optional_switch::OptionalBool;
+use crate::gui::widgets::optional_switch::{switch, OptionalBool};
 use egui::Ui;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Default)]
 pub struct ItemTags {
@@ -14,8 +16,8 @@ pub struct ItemTags {
     pub special_offer: bool,
 }
 
-impl ToString for ItemTags {
-    fn to_string(&self) -> String {
+impl Display for ItemTags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut result = Vec::new();
 
         if self.dragon_amulet {
@@ -37,11 +39,11 @@ impl ToString for ItemTags {
             result.push("Special Offer");
         }
 
-        result.join(", ")
+        write!(f, "{}", result.join(", "))
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ItemFilters {
     pub dragon_coins: OptionalBool,
     pub dragon_amulet: OptionalBool,
@@ -52,6 +54,37 @@ pub struct ItemFilters {
 }
 
 impl ItemFilters {
+    // Returns whether a filter changed
+    pub fn draw(&mut self, ui: &mut Ui, id: &str) -> bool {
+        let mut changed = false;
+        egui::Grid::new(id).show(ui, |ui| {
+            ui.label("Dragon Amulet");
+            changed |= switch(ui, &mut self.dragon_amulet).changed();
+            ui.end_row();
+
+            ui.label("Dragon Coins");
+            changed |= switch(ui, &mut self.dragon_coins).changed();
+            ui.end_row();
+
+            ui.label("Rare");
+            changed |= switch(ui, &mut self.rare).changed();
+            ui.end_row();
+
+            ui.label("Temporary");
+            changed |= switch(ui, &mut self.temporary).changed();
+            ui.end_row();
+
+            ui.label("Special Offer");
+            changed |= switch(ui, &mut self.special_offer).changed();
+            ui.end_row();
+
+            ui.label("War");
+            changed |= switch(ui, &mut self.war).changed();
+        });
+
+        changed
+    }
+
     pub fn allow(&self, tags: &ItemTags) -> bool {
         if let Some(val) = self.dragon_coins.as_option() {
             if tags.dragon_coins != val {
@@ -88,6 +121,55 @@ impl ItemFilters {
     }
 }
 
+// What the item list is sorted by, bonus and resist keys are the keys of the items' `Dict`s
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Level,
+    Bonus(String),
+    Resist(String),
+}
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortKey::Name => write!(f, "Name"),
+            SortKey::Level => write!(f, "Level"),
+            SortKey::Bonus(key) => write!(f, "{}", key),
+            SortKey::Resist(key) => write!(f, "{} resist", key),
+        }
+    }
+}
+
+impl SortKey {
+    // The name and level, followed by every bonus and resist key that appears in `items`
+    pub fn vec<'a>(items: impl Iterator<Item = &'a GuiItem>) -> Vec<SortKey> {
+        let mut bonuses = BTreeSet::new();
+        let mut resists = BTreeSet::new();
+        for item in items {
+            bonuses.extend(item.base.bonuses.keys().cloned());
+            resists.extend(item.base.resists.keys().cloned());
+        }
+
+        let mut result = vec![SortKey::Name, SortKey::Level];
+        result.extend(bonuses.into_iter().map(SortKey::Bonus));
+        result.extend(resists.into_iter().map(SortKey::Resist));
+
+        result
+    }
+
+    // Ascending order, items without the key count as 0
+    pub fn compare(&self, a: &GuiItem, b: &GuiItem) -> Ordering {
+        match self {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Level => a.base.level.cmp(&b.base.level),
+            SortKey::Bonus(key) => a.base.bonuses.get(key).total_cmp(&b.base.bonuses.get(key)),
+            SortKey::Resist(key) => a.base.resists.get(key).total_cmp(&b.base.resists.get(key)),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct GuiItem {
     pub base: Item,
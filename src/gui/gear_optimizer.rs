@@ -0,0 +1,508 @@
+use crate::game::entities::entity::EntityMutRef::PlayerMutRef;
+use crate::game::entities::entity::{Entity, EntityTrait};
+use crate::game::fight_record::{FightRecord, FightSummary, MAX_TURNS};
+use crate::game::game_manager::GameManager;
+use crate::game::rotation::Rotation;
+use crate::game::types::gear::Slot;
+use crate::gui::gear::{GearSet, GuiItem, ItemFilters};
+use crate::gui::optimizer_task::{Optimizer, TaskProgress};
+use crate::gui::options::GameOptions;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+
+// Bonuses that change the damage of an attack
+const DAMAGE_BONUSES: [&str; 9] = [
+    "STR",
+    "DEX",
+    "INT",
+    "LUK",
+    "WIS",
+    "crit",
+    "bonus",
+    "boost",
+    "crit_modifier_bonus",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptimizerObjective {
+    // Expected damage of a basic attack against the first enemy
+    AttackDamage,
+
+    // Resistance to an element, ties are broken by basic attack damage
+    Resist(String),
+
+    // Win rate of a rotation over several fights.
+    // Simulating every build would take too long, so the search keeps the builds with the most basic
+    // attack damage, and only the builds it ends with are ranked by win rate.
+    WinRate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptimizerError {
+    // `OptimizerObjective::WinRate` needs a rotation to simulate
+    MissingRotation,
+    NoItems,
+}
+
+impl Display for OptimizerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizerError::MissingRotation => {
+                write!(f, "Optimizing the win rate needs a rotation")
+            }
+            OptimizerError::NoItems => write!(f, "No items pass the filters"),
+        }
+    }
+}
+
+impl OptimizerObjective {
+    // Resists are discovered from the items
+    pub fn vec<'a>(items: impl Iterator<Item = &'a GuiItem>) -> Vec<OptimizerObjective> {
+        let mut resists = BTreeSet::new();
+        for item in items {
+            resists.extend(item.base.resists.keys().cloned());
+        }
+
+        let mut result = vec![
+            OptimizerObjective::AttackDamage,
+            OptimizerObjective::WinRate,
+        ];
+        result.extend(resists.into_iter().map(OptimizerObjective::Resist));
+
+        result
+    }
+}
+
+impl Display for OptimizerObjective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizerObjective::AttackDamage => write!(f, "Basic Attack Damage"),
+            OptimizerObjective::Resist(elem) => write!(f, "Resist: {}", elem),
+            OptimizerObjective::WinRate => write!(f, "Win Rate"),
+        }
+    }
+}
+
+pub struct OptimizerSettings {
+    pub objective: OptimizerObjective,
+
+    pub filters: ItemFilters,
+
+    // Only use items in `GearOptions::owned_items`
+    pub owned_only: bool,
+    pub max_level: u8,
+
+    // Number of builds to report
+    pub results: usize,
+
+    // Number of partial builds kept after every slot
+    pub beam_width: usize,
+
+    // Only used by `OptimizerObjective::WinRate`
+    pub rotation: Option<Rotation>,
+    pub fights: usize,
+}
+
+pub struct OptimizedBuild {
+    pub gear_set: GearSet,
+
+    // Basic attack damage, resistance or win rate, depending on the objective
+    pub score: f64,
+    pub attack_damage: f64,
+}
+
+impl OptimizedBuild {
+    fn compare(&self, other: &OptimizedBuild) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.attack_damage.total_cmp(&other.attack_damage))
+    }
+}
+
+// A build that is still missing some slots
+struct PartialBuild {
+    gear_set: GearSet,
+    player: Box<dyn EntityTrait + Send>,
+    score: f64,
+    attack_damage: f64,
+}
+
+impl PartialBuild {
+    fn compare(&self, other: &PartialBuild) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.attack_damage.total_cmp(&other.attack_damage))
+    }
+}
+
+// Picks one item per slot, searching the slots one by one and keeping the best `beam_width` builds.
+// Stat bonuses are floored and gear bonuses are capped, so the result isn't guaranteed to be optimal.
+// Every step searches a slot, or simulates the fights of a build once every slot was searched.
+pub struct GearOptimizer {
+    game_options: GameOptions,
+    settings: OptimizerSettings,
+    enemy: Entity,
+
+    // The items that pass the filters, for every slot that has some.
+    // Dominated items are pruned when their slot is searched.
+    slots: Vec<(Slot, Vec<GuiItem>)>,
+    searched_slots: usize,
+
+    builds: Vec<PartialBuild>,
+
+    // The builds that were scored, once every slot was searched
+    results: Vec<OptimizedBuild>,
+}
+
+impl GearOptimizer {
+    pub fn new(
+        game_options: GameOptions,
+        settings: OptimizerSettings,
+        items: &HashMap<Slot, Vec<GuiItem>>,
+        owned_items: &BTreeSet<u32>,
+    ) -> Result<Self, OptimizerError> {
+        if settings.objective == OptimizerObjective::WinRate && settings.rotation.is_none() {
+            return Err(OptimizerError::MissingRotation);
+        }
+
+        let slots: Vec<(Slot, Vec<GuiItem>)> = Slot::vec()
+            .into_iter()
+            .filter_map(|slot| {
+                let items: Vec<GuiItem> = items
+                    .get(&slot)?
+                    .iter()
+                    .filter(|item| {
+                        settings.filters.allow(&item.tags)
+                            && item.base.level <= settings.max_level
+                            && (!settings.owned_only || owned_items.contains(&item.pedia_page_id))
+                    })
+                    .cloned()
+                    .collect();
+
+                (!items.is_empty()).then_some((slot, items))
+            })
+            .collect();
+        if slots.is_empty() {
+            return Err(OptimizerError::NoItems);
+        }
+
+        let enemies = game_options.challenge.create(game_options.level);
+        let mut optimizer = Self {
+            enemy: enemies[0].get_base_entity().clone(),
+            builds: Vec::new(),
+            slots,
+            searched_slots: 0,
+            results: Vec::new(),
+            game_options,
+            settings,
+        };
+
+        let build = optimizer.score(PartialBuild {
+            gear_set: GearSet::new(String::new()),
+            player: optimizer.game_options.create_player(None),
+            score: 0.0,
+            attack_damage: 0.0,
+        });
+        optimizer.builds.push(build);
+
+        Ok(optimizer)
+    }
+
+    // Extends every build with every item of the next slot, and keeps the best ones
+    fn search_slot(&mut self) {
+        let (slot, items) = &mut self.slots[self.searched_slots];
+        let (slot, items) = (*slot, std::mem::take(items));
+        let candidates = self.candidates(items);
+
+        let mut next_builds = Vec::new();
+        for build in &self.builds {
+            for item in &candidates {
+                let mut player = build.player.clone_box();
+                if let PlayerMutRef(player) = player.get_base_type_mut() {
+                    player.equip(item.base.clone());
+                }
+
+                let mut gear_set = build.gear_set.clone();
+                gear_set.set.insert(slot, item.clone());
+
+                next_builds.push(self.score(PartialBuild {
+                    gear_set,
+                    player,
+                    score: 0.0,
+                    attack_damage: 0.0,
+                }));
+            }
+        }
+
+        next_builds.sort_by(|a, b| b.compare(a));
+        next_builds.truncate(self.settings.beam_width.max(self.settings.results));
+        self.builds = next_builds;
+        self.searched_slots += 1;
+    }
+
+    // The best of the builds that were scored, best first
+    fn finish(&mut self) -> Vec<OptimizedBuild> {
+        let mut results = std::mem::take(&mut self.results);
+        results.sort_by(|a, b| b.compare(a));
+        results.truncate(self.settings.results);
+
+        results
+    }
+
+    // Removes the dominated items of a slot
+    fn candidates(&self, items: Vec<GuiItem>) -> Vec<GuiItem> {
+        let (bonus_keys, resist_keys): (Vec<String>, Vec<String>) = match &self.settings.objective {
+            OptimizerObjective::AttackDamage | OptimizerObjective::Resist(_) => (
+                DAMAGE_BONUSES.iter().map(|key| key.to_string()).collect(),
+                Vec::new(),
+            ),
+            // Every bonus and resist might matter in a fight
+            OptimizerObjective::WinRate => {
+                let mut bonus_keys = BTreeSet::new();
+                let mut resist_keys = BTreeSet::new();
+                for item in &items {
+                    bonus_keys.extend(item.base.bonuses.keys().cloned());
+                    resist_keys.extend(item.base.resists.keys().cloned());
+                }
+
+                (
+                    bonus_keys.into_iter().collect(),
+                    resist_keys.into_iter().collect(),
+                )
+            }
+        };
+        let features: Vec<Vec<f32>> = items
+            .iter()
+            .map(|item| self.features(item, &bonus_keys, &resist_keys))
+            .collect();
+
+        let dominated: Vec<bool> = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                items.iter().enumerate().any(|(other_index, other)| {
+                    other_index != index
+                        && other.base.dmg_type == item.base.dmg_type
+                        && Self::dominates(
+                            &features[other_index],
+                            &features[index],
+                            other_index < index,
+                        )
+                })
+            })
+            .collect();
+
+        items
+            .into_iter()
+            .zip(dominated)
+            .filter(|(_, dominated)| !dominated)
+            .map(|(item, _)| item)
+            .collect()
+    }
+
+    // The values of an item that matter for the objective, higher is better
+    fn features(&self, item: &GuiItem, bonus_keys: &[String], resist_keys: &[String]) -> Vec<f32> {
+        let mut result = Vec::new();
+        if let Some(dmg) = item.base.dmg {
+            result.push(dmg.min);
+            result.push(dmg.max);
+        }
+
+        result.extend(bonus_keys.iter().map(|key| item.base.bonuses.get(key)));
+        result.extend(resist_keys.iter().map(|key| {
+            // Negative health resistance increases healing
+            if key == "health" {
+                -item.base.resists.get(key)
+            } else {
+                item.base.resists.get(key)
+            }
+        }));
+
+        if let OptimizerObjective::Resist(elem) = &self.settings.objective {
+            let mut resist = item.base.resists.get(elem);
+            if elem != "all" {
+                resist += item.base.resists.get("all");
+            }
+            result.push(resist);
+        }
+
+        result
+    }
+
+    // Whether `a` is at least as good as `b` everywhere, and better somewhere.
+    // Items with the same values dominate each other, so `tie_break` decides which one is kept.
+    fn dominates(a: &[f32], b: &[f32], tie_break: bool) -> bool {
+        if a.len() != b.len() || a.iter().zip(b).any(|(a, b)| a < b) {
+            return false;
+        }
+
+        tie_break || a.iter().zip(b).any(|(a, b)| a > b)
+    }
+
+    fn score(&self, mut build: PartialBuild) -> PartialBuild {
+        let player = build.player.get_base_entity_mut();
+        build.attack_damage = player.expected_attack_damage(&self.enemy) as f64;
+        build.score = match &self.settings.objective {
+            OptimizerObjective::Resist(elem) => player.get_resist(elem) as f64,
+            _ => build.attack_damage,
+        };
+
+        build
+    }
+
+    fn win_rate(&self, rotation: &Rotation, player: &(dyn EntityTrait + Send)) -> f64 {
+        let game = GameManager::new(
+            player.clone_box(),
+            self.game_options.challenge.create(self.game_options.level),
+        );
+        let records: Vec<FightRecord> = (0..self.settings.fights)
+            .map(|_| rotation.play_fight(game.clone(), MAX_TURNS))
+            .collect();
+
+        FightSummary::from(records.as_slice()).win_rate
+    }
+}
+
+impl Optimizer for GearOptimizer {
+    type Output = Vec<OptimizedBuild>;
+
+    fn step(&mut self) -> Option<Vec<OptimizedBuild>> {
+        if self.searched_slots < self.slots.len() {
+            self.search_slot();
+            return None;
+        }
+
+        let Some(build) = self.builds.pop() else {
+            return Some(self.finish());
+        };
+        let score = match (&self.settings.objective, &self.settings.rotation) {
+            (OptimizerObjective::WinRate, Some(rotation)) => {
+                self.win_rate(rotation, build.player.as_ref())
+            }
+            _ => build.score,
+        };
+        self.results.push(OptimizedBuild {
+            gear_set: build.gear_set,
+            score,
+            attack_damage: build.attack_damage,
+        });
+
+        None
+    }
+
+    fn progress(&self) -> TaskProgress {
+        if self.searched_slots < self.slots.len() {
+            return TaskProgress {
+                fraction: self.searched_slots as f32 / self.slots.len() as f32,
+                text: format!(
+                    "Searching slot {} / {}",
+                    self.searched_slots + 1,
+                    self.slots.len()
+                ),
+            };
+        }
+
+        let builds = self.results.len() + self.builds.len();
+        TaskProgress {
+            fraction: self.results.len() as f32 / builds.max(1) as f32,
+            text: format!("Scoring build {} / {}", self.results.len() + 1, builds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::types::damage::{DamageRange, DamageType};
+    use crate::game::types::dict::Dict;
+    use crate::game::types::gear::Item;
+    use crate::gui::gear::ItemTags;
+
+    fn create_settings(
+        objective: OptimizerObjective,
+        rotation: Option<Rotation>,
+    ) -> OptimizerSettings {
+        OptimizerSettings {
+            objective,
+            filters: ItemFilters::default(),
+            owned_only: false,
+            max_level: 90,
+            results: 5,
+            beam_width: 50,
+            rotation,
+            fights: 1,
+        }
+    }
+
+    fn item(slot: Slot, name: &str, dmg: Option<f32>, bonuses: Dict) -> GuiItem {
+        GuiItem {
+            base: Item {
+                slot,
+                level: 1,
+                dmg: dmg.map(DamageRange::from),
+                dmg_type: dmg.map(|_| DamageType::Melee),
+                bonuses,
+                resists: Dict::new(),
+            },
+            name: name.to_string(),
+            pedia_page_id: 0,
+            weapon_type: None,
+            tags: ItemTags::default(),
+        }
+    }
+
+    fn run(mut optimizer: GearOptimizer) -> Vec<OptimizedBuild> {
+        loop {
+            if let Some(builds) = optimizer.step() {
+                return builds;
+            }
+        }
+    }
+
+    #[test]
+    fn win_rate_needs_a_rotation() {
+        let game_options = GameOptions::default();
+        let items = HashMap::new();
+
+        let settings = create_settings(OptimizerObjective::WinRate, None);
+        let result = GearOptimizer::new(game_options.clone(), settings, &items, &BTreeSet::new());
+        assert_eq!(result.err(), Some(OptimizerError::MissingRotation));
+
+        let rotation = Rotation { skills: vec![0] };
+        let settings = create_settings(OptimizerObjective::WinRate, Some(rotation));
+        let result = GearOptimizer::new(game_options, settings, &items, &BTreeSet::new());
+        assert_eq!(result.err(), Some(OptimizerError::NoItems));
+    }
+
+    #[test]
+    fn dominated_items_are_pruned() {
+        let items = HashMap::from([
+            (
+                Slot::Weapon,
+                vec![
+                    item(Slot::Weapon, "Stick", Some(10.0), Dict::new()),
+                    item(Slot::Weapon, "Sword", Some(50.0), Dict::new()),
+                ],
+            ),
+            (
+                Slot::Ring,
+                vec![
+                    item(Slot::Ring, "Ring", None, Dict::from([("STR", 10.0)])),
+                    item(Slot::Ring, "Better Ring", None, Dict::from([("STR", 20.0)])),
+                ],
+            ),
+        ]);
+
+        let settings = create_settings(OptimizerObjective::AttackDamage, None);
+        let optimizer =
+            GearOptimizer::new(GameOptions::default(), settings, &items, &BTreeSet::new()).unwrap();
+        let builds = run(optimizer);
+
+        // Only the best item of every slot is left, so there's a single build
+        assert_eq!(builds.len(), 1);
+        assert_eq!(builds[0].gear_set.set[&Slot::Weapon].name, "Sword");
+        assert_eq!(builds[0].gear_set.set[&Slot::Ring].name, "Better Ring");
+        assert_eq!(builds[0].score, builds[0].attack_damage);
+    }
+}
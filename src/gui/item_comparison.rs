@@ -0,0 +1,163 @@
+use crate::gui::gear::{GearSet, GuiItem};
+use crate::gui::options::GameOptions;
+use crate::gui::utils;
+use egui::{Color32, Ui};
+use std::collections::BTreeSet;
+
+// Bonuses and resists that change with stats, even when an item doesn't have them
+const DERIVED_BONUSES: [&str; 5] = ["crit", "melee_def", "pierce_def", "magic_def", "bonus"];
+const DERIVED_RESISTS: [&str; 2] = ["immobility", "health"];
+
+// A value of the player with the equipped item, and with the compared item
+pub struct StatDelta {
+    pub name: String,
+    pub before: f32,
+    pub after: f32,
+
+    // Negative health resistance increases healing
+    pub lower_is_better: bool,
+}
+
+impl StatDelta {
+    fn new(name: &str, before: f32, after: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            before,
+            after,
+            lower_is_better: false,
+        }
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.after - self.before
+    }
+
+    pub fn is_improvement(&self) -> bool {
+        (self.delta() > 0.0) != self.lower_is_better
+    }
+}
+
+// The changes to the player when an item replaces the one equipped in its slot
+pub struct ItemComparison {
+    // The item equipped in the slot, if any
+    pub equipped: Option<String>,
+
+    pub general: Vec<StatDelta>,
+    pub bonuses: Vec<StatDelta>,
+    pub resists: Vec<StatDelta>,
+}
+
+impl ItemComparison {
+    pub fn new(game_options: &GameOptions, gear_set: Option<&GearSet>, item: &GuiItem) -> Self {
+        let slot = item.base.slot;
+
+        let mut new_gear_set = gear_set
+            .cloned()
+            .unwrap_or_else(|| GearSet::new(String::new()));
+        new_gear_set.set.insert(slot, item.clone());
+
+        let mut before = game_options.create_player(gear_set);
+        let mut after = game_options.create_player(Some(&new_gear_set));
+
+        // Expected damage of a single attack against the first enemy of the challenge
+        let enemies = game_options.challenge.create(game_options.level);
+        let enemy = enemies[0].get_base_entity();
+
+        let before_damage = before.get_base_entity_mut().expected_attack_damage(enemy);
+        let after_damage = after.get_base_entity_mut().expected_attack_damage(enemy);
+
+        let before = before.get_base_entity();
+        let after = after.get_base_entity();
+
+        let general = vec![
+            StatDelta::new("HP", before.max_hp as f32, after.max_hp as f32),
+            StatDelta::new("MP", before.max_mp as f32, after.max_mp as f32),
+            StatDelta::new("Basic attack damage", before_damage, after_damage),
+        ];
+
+        let bonus_keys: BTreeSet<&str> = before
+            .gear_bonuses
+            .keys()
+            .chain(after.gear_bonuses.keys())
+            .map(|key| key.as_str())
+            .chain(DERIVED_BONUSES)
+            .collect();
+        let bonuses = bonus_keys
+            .into_iter()
+            .map(|key| StatDelta::new(key, before.get_bonus(key), after.get_bonus(key)))
+            .filter(|delta| delta.delta() != 0.0)
+            .collect();
+
+        let resist_keys: BTreeSet<&str> = before
+            .gear_resists
+            .keys()
+            .chain(after.gear_resists.keys())
+            .map(|key| key.as_str())
+            .chain(DERIVED_RESISTS)
+            .collect();
+        let resists = resist_keys
+            .into_iter()
+            .map(|key| {
+                let mut delta = StatDelta::new(key, before.get_resist(key), after.get_resist(key));
+                delta.lower_is_better = key == "health";
+                delta
+            })
+            .filter(|delta| delta.delta() != 0.0)
+            .collect();
+
+        Self {
+            equipped: gear_set
+                .and_then(|gear_set| gear_set.set.get(&slot))
+                .map(|item| item.name.clone()),
+            general,
+            bonuses,
+            resists,
+        }
+    }
+
+    pub fn draw(&self, ui: &mut Ui) {
+        match &self.equipped {
+            Some(name) => ui.heading(format!("Compared to {}", name)),
+            None => ui.heading("Compared to an empty slot"),
+        };
+
+        egui::Grid::new("item_comparison_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Equipped");
+                ui.label("New");
+                ui.label("Change");
+                ui.end_row();
+
+                for delta in self.general.iter().chain(&self.bonuses) {
+                    Self::draw_delta(ui, delta, "");
+                }
+                for delta in &self.resists {
+                    Self::draw_delta(ui, delta, "%");
+                }
+            });
+    }
+
+    fn draw_delta(ui: &mut Ui, delta: &StatDelta, suffix: &str) {
+        ui.label(format!("{}:", utils::to_title_case(&delta.name)));
+        ui.label(format!("{}{}", Self::format_value(delta.before), suffix));
+        ui.label(format!("{}{}", Self::format_value(delta.after), suffix));
+
+        let change = format!("{:+}{}", Self::format_value(delta.delta()), suffix);
+        if delta.delta() == 0.0 {
+            ui.label(change);
+        } else if delta.is_improvement() {
+            ui.colored_label(Color32::GREEN, change);
+        } else {
+            ui.colored_label(Color32::RED, change);
+        }
+        ui.end_row();
+    }
+
+    // Damage isn't a whole number, the other values are
+    fn format_value(value: f32) -> f32 {
+        (value * 10.0).round() / 10.0
+    }
+}
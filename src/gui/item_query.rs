@@ -0,0 +1,543 @@
+use crate::game::types::dict::Dict;
+use crate::gui::gear::{GuiItem, ItemTags};
+use std::fmt::{Display, Formatter};
+
+// Tags that can be used on their own as conditions, e.g. `not rare`
+const TAGS: [&str; 6] = [
+    "dragon_amulet",
+    "dragon_coins",
+    "temporary",
+    "rare",
+    "war",
+    "special_offer",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    // The column the error was found at, starting from 1
+    pub column: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(column: usize, message: String) -> Self {
+        Self { column, message }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComparisonOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+
+    // Case insensitive substring, for text fields
+    Contains,
+}
+
+impl ComparisonOp {
+    fn from_str(op: &str) -> Option<Self> {
+        Some(match op {
+            "=" | "==" => ComparisonOp::Equal,
+            "!=" => ComparisonOp::NotEqual,
+            "<" => ComparisonOp::Less,
+            "<=" => ComparisonOp::LessOrEqual,
+            ">" => ComparisonOp::Greater,
+            ">=" => ComparisonOp::GreaterOrEqual,
+            "~" => ComparisonOp::Contains,
+            _ => return None,
+        })
+    }
+
+    fn compare_numbers(&self, a: f32, b: f32) -> bool {
+        match self {
+            ComparisonOp::Equal => a == b,
+            ComparisonOp::NotEqual => a != b,
+            ComparisonOp::Less => a < b,
+            ComparisonOp::LessOrEqual => a <= b,
+            ComparisonOp::Greater => a > b,
+            ComparisonOp::GreaterOrEqual => a >= b,
+            ComparisonOp::Contains => false,
+        }
+    }
+
+    // Text is compared case insensitively, `b` is already lowercase
+    fn compare_text(&self, a: &str, b: &str) -> bool {
+        let a = a.to_lowercase();
+        match self {
+            ComparisonOp::Equal => a == b,
+            ComparisonOp::NotEqual => a != b,
+            ComparisonOp::Contains => a.contains(b),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberField {
+    Level,
+    PediaPageId,
+    Bonus(String),
+    Resist(String),
+}
+
+impl NumberField {
+    fn get(&self, item: &GuiItem) -> f32 {
+        match self {
+            NumberField::Level => item.base.level as f32,
+            NumberField::PediaPageId => item.pedia_page_id as f32,
+            NumberField::Bonus(key) => dict_value(&item.base.bonuses, key),
+            NumberField::Resist(key) => dict_value(&item.base.resists, key),
+        }
+    }
+}
+
+// Keys are matched case insensitively, so `bonuses.str` finds STR
+fn dict_value(dict: &Dict, key: &str) -> f32 {
+    match dict.keys().find(|name| name.eq_ignore_ascii_case(key)) {
+        Some(name) => dict.get(name),
+        None => dict.get(key),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextField {
+    Name,
+    WeaponType,
+}
+
+impl TextField {
+    // Items without a weapon type have an empty one
+    fn get<'a>(&self, item: &'a GuiItem) -> &'a str {
+        match self {
+            TextField::Name => &item.name,
+            TextField::WeaponType => item.weapon_type.as_deref().unwrap_or(""),
+        }
+    }
+}
+
+// A parsed filter such as `level>=80 and bonuses.crit>=5 and not rare`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemQuery {
+    And(Box<ItemQuery>, Box<ItemQuery>),
+    Or(Box<ItemQuery>, Box<ItemQuery>),
+    Not(Box<ItemQuery>),
+    Tag(String),
+    Number {
+        field: NumberField,
+        op: ComparisonOp,
+        value: f32,
+    },
+    Text {
+        field: TextField,
+        op: ComparisonOp,
+        value: String,
+    },
+}
+
+impl ItemQuery {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            index: 0,
+            end_column: text.chars().count() + 1,
+        };
+
+        let query = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(QueryError::new(
+                token.column,
+                format!("Expected \"and\" or \"or\", found \"{}\"", token.text),
+            ));
+        }
+
+        Ok(query)
+    }
+
+    pub fn matches(&self, item: &GuiItem) -> bool {
+        match self {
+            ItemQuery::And(a, b) => a.matches(item) && b.matches(item),
+            ItemQuery::Or(a, b) => a.matches(item) || b.matches(item),
+            ItemQuery::Not(query) => !query.matches(item),
+            ItemQuery::Tag(tag) => has_tag(&item.tags, tag),
+            ItemQuery::Number { field, op, value } => op.compare_numbers(field.get(item), *value),
+            ItemQuery::Text { field, op, value } => op.compare_text(field.get(item), value),
+        }
+    }
+}
+
+fn has_tag(tags: &ItemTags, tag: &str) -> bool {
+    match tag {
+        "dragon_amulet" => tags.dragon_amulet,
+        "dragon_coins" => tags.dragon_coins,
+        "temporary" => tags.temporary,
+        "rare" => tags.rare,
+        "war" => tags.war,
+        "special_offer" => tags.special_offer,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    // Field names, tags, keywords and unquoted values
+    Word,
+    Number(f32),
+    Quoted,
+    Op,
+    OpenParen,
+    CloseParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    column: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let column = index + 1;
+        let start = index;
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let kind = if c == '(' {
+            index += 1;
+            TokenKind::OpenParen
+        } else if c == ')' {
+            index += 1;
+            TokenKind::CloseParen
+        } else if "=!<>~".contains(c) {
+            index += 1;
+            if index < chars.len() && chars[index] == '=' {
+                index += 1;
+            }
+            TokenKind::Op
+        } else if c == '"' {
+            index += 1;
+            while index < chars.len() && chars[index] != '"' {
+                index += 1;
+            }
+            if index == chars.len() {
+                return Err(QueryError::new(column, "Unclosed quote".to_string()));
+            }
+            index += 1;
+            TokenKind::Quoted
+        } else if c.is_alphanumeric() || "_.-+".contains(c) {
+            while index < chars.len()
+                && (chars[index].is_alphanumeric() || "_.-+".contains(chars[index]))
+            {
+                index += 1;
+            }
+            let word: String = chars[start..index].iter().collect();
+            match word.parse() {
+                Ok(number) => TokenKind::Number(number),
+                Err(_) => TokenKind::Word,
+            }
+        } else {
+            return Err(QueryError::new(column, format!("Unexpected \"{}\"", c)));
+        };
+
+        let mut text: String = chars[start..index].iter().collect();
+        if kind == TokenKind::Quoted {
+            text = text[1..text.len() - 1].to_string();
+        }
+        tokens.push(Token { kind, text, column });
+    }
+
+    Ok(tokens)
+}
+
+// A recursive descent parser, `and` binds tighter than `or`
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+
+    // Where errors at the end of the query are reported
+    end_column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, QueryError> {
+        let token =
+            self.tokens.get(self.index).cloned().ok_or_else(|| {
+                QueryError::new(self.end_column, format!("Expected {}", expected))
+            })?;
+        self.index += 1;
+
+        Ok(token)
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(token) if token.kind == TokenKind::Word && token.text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<ItemQuery, QueryError> {
+        let mut query = self.parse_and()?;
+        while self.next_is_keyword("or") {
+            self.index += 1;
+            query = ItemQuery::Or(Box::new(query), Box::new(self.parse_and()?));
+        }
+
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<ItemQuery, QueryError> {
+        let mut query = self.parse_unary()?;
+        while self.next_is_keyword("and") {
+            self.index += 1;
+            query = ItemQuery::And(Box::new(query), Box::new(self.parse_unary()?));
+        }
+
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> Result<ItemQuery, QueryError> {
+        if self.next_is_keyword("not") {
+            self.index += 1;
+            return Ok(ItemQuery::Not(Box::new(self.parse_unary()?)));
+        }
+
+        let token = self.next("a condition")?;
+        match token.kind {
+            TokenKind::OpenParen => {
+                let query = self.parse_or()?;
+                let close = self.next("\")\"")?;
+                if close.kind != TokenKind::CloseParen {
+                    return Err(QueryError::new(
+                        close.column,
+                        format!("Expected \")\", found \"{}\"", close.text),
+                    ));
+                }
+
+                Ok(query)
+            }
+            TokenKind::Word => self.parse_condition(token),
+            _ => Err(QueryError::new(
+                token.column,
+                format!("Expected a condition, found \"{}\"", token.text),
+            )),
+        }
+    }
+
+    // A tag, or a field compared to a value
+    fn parse_condition(&mut self, field: Token) -> Result<ItemQuery, QueryError> {
+        let name = field.text.to_lowercase();
+        let is_comparison = matches!(self.peek(), Some(token) if token.kind == TokenKind::Op);
+
+        if !is_comparison {
+            if TAGS.contains(&name.as_str()) {
+                return Ok(ItemQuery::Tag(name));
+            }
+            return Err(QueryError::new(
+                field.column,
+                format!(
+                    "\"{}\" is not a tag, tags are {}",
+                    field.text,
+                    TAGS.join(", ")
+                ),
+            ));
+        }
+
+        let op_token = self.next("an operator")?;
+        let op = ComparisonOp::from_str(&op_token.text).ok_or_else(|| {
+            QueryError::new(
+                op_token.column,
+                format!("Unknown operator \"{}\"", op_token.text),
+            )
+        })?;
+        let value = self.next("a value")?;
+        if !matches!(
+            value.kind,
+            TokenKind::Word | TokenKind::Number(_) | TokenKind::Quoted
+        ) {
+            return Err(QueryError::new(
+                value.column,
+                format!("Expected a value, found \"{}\"", value.text),
+            ));
+        }
+
+        let text_field = match name.as_str() {
+            "name" => Some(TextField::Name),
+            "weapon_type" => Some(TextField::WeaponType),
+            _ => None,
+        };
+        if let Some(field) = text_field {
+            if !matches!(
+                op,
+                ComparisonOp::Equal | ComparisonOp::NotEqual | ComparisonOp::Contains
+            ) {
+                return Err(QueryError::new(
+                    op_token.column,
+                    format!("{} can only be compared with =, != or ~", name),
+                ));
+            }
+            return Ok(ItemQuery::Text {
+                field,
+                op,
+                value: value.text.to_lowercase(),
+            });
+        }
+
+        let number_field = if name.starts_with("bonuses.") {
+            NumberField::Bonus(field.text["bonuses.".len()..].to_string())
+        } else if name.starts_with("resists.") {
+            NumberField::Resist(field.text["resists.".len()..].to_string())
+        } else {
+            match name.as_str() {
+                "level" => NumberField::Level,
+                "pedia_page_id" => NumberField::PediaPageId,
+                _ => {
+                    return Err(QueryError::new(
+                        field.column,
+                        format!(
+                            "Unknown field \"{}\", fields are name, weapon_type, level, pedia_page_id, bonuses.<key> and resists.<key>",
+                            field.text
+                        ),
+                    ))
+                }
+            }
+        };
+        if op == ComparisonOp::Contains {
+            return Err(QueryError::new(
+                op_token.column,
+                "~ can only be used with text fields".to_string(),
+            ));
+        }
+        let TokenKind::Number(number) = value.kind else {
+            return Err(QueryError::new(
+                value.column,
+                format!("Expected a number, found \"{}\"", value.text),
+            ));
+        };
+
+        Ok(ItemQuery::Number {
+            field: number_field,
+            op,
+            value: number,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> Box<ItemQuery> {
+        Box::new(ItemQuery::Tag(name.to_string()))
+    }
+
+    fn level_at_least(value: f32) -> Box<ItemQuery> {
+        Box::new(ItemQuery::Number {
+            field: NumberField::Level,
+            op: ComparisonOp::GreaterOrEqual,
+            value,
+        })
+    }
+
+    fn error(text: &str) -> QueryError {
+        ItemQuery::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            ItemQuery::parse("rare or war and level>=80"),
+            Ok(ItemQuery::Or(
+                tag("rare"),
+                Box::new(ItemQuery::And(tag("war"), level_at_least(80.0)))
+            ))
+        );
+        assert_eq!(
+            ItemQuery::parse("not rare and war"),
+            Ok(ItemQuery::And(
+                Box::new(ItemQuery::Not(tag("rare"))),
+                tag("war")
+            ))
+        );
+    }
+
+    #[test]
+    fn parentheses_group_conditions() {
+        assert_eq!(
+            ItemQuery::parse("(rare or war) and level >= 80"),
+            Ok(ItemQuery::And(
+                Box::new(ItemQuery::Or(tag("rare"), tag("war"))),
+                level_at_least(80.0)
+            ))
+        );
+        assert_eq!(
+            ItemQuery::parse("not (rare or war)"),
+            Ok(ItemQuery::Not(Box::new(ItemQuery::Or(
+                tag("rare"),
+                tag("war")
+            ))))
+        );
+
+        assert_eq!(
+            error("(rare"),
+            QueryError::new(6, "Expected \")\"".to_string())
+        );
+        assert_eq!(error("rare)").column, 5);
+    }
+
+    #[test]
+    fn unknown_fields_and_tags_are_rejected() {
+        let unknown_field = error("power >= 5");
+        assert_eq!(unknown_field.column, 1);
+        assert!(unknown_field.message.starts_with("Unknown field \"power\""));
+
+        let unknown_tag = error("rare and shiny");
+        assert_eq!(unknown_tag.column, 10);
+        assert!(unknown_tag.message.starts_with("\"shiny\" is not a tag"));
+
+        // Bonus and resist keys aren't checked, items without them have 0
+        assert!(ItemQuery::parse("bonuses.anything > 0").is_ok());
+    }
+
+    #[test]
+    fn number_fields_need_numbers() {
+        assert_eq!(
+            error("level>=abc"),
+            QueryError::new(8, "Expected a number, found \"abc\"".to_string())
+        );
+        assert_eq!(error("level >= 1.2.3").column, 10);
+        assert_eq!(error("level ~ 5").column, 7);
+        assert_eq!(
+            error("level >="),
+            QueryError::new(9, "Expected a value".to_string())
+        );
+        assert_eq!(
+            ItemQuery::parse("bonuses.crit >= -2.5"),
+            Ok(ItemQuery::Number {
+                field: NumberField::Bonus("crit".to_string()),
+                op: ComparisonOp::GreaterOrEqual,
+                value: -2.5,
+            })
+        );
+    }
+}
@@ -1,8 +1,19 @@
 mod app;
+mod build_code;
+mod build_summary;
 mod gear;
+mod gear_optimizer;
 pub mod gui_manager;
+mod item_comparison;
+mod item_query;
+mod optimizer_task;
 mod options;
+mod saved_state;
+mod sensitivity;
+mod stat_optimizer;
 mod stats_handler;
+#[cfg(target_arch = "wasm32")]
+mod url_state;
 mod utils;
 mod views;
 mod widgets;
@@ -0,0 +1,130 @@
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+use std::sync::mpsc::TryRecvError;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
+
+// How long the web build runs an optimizer between two frames
+#[cfg(target_arch = "wasm32")]
+const FRAME_BUDGET: Duration = Duration::from_millis(20);
+
+// An optimization that's done one small step at a time, so it can report its progress and be stopped
+pub trait Optimizer: Send + 'static {
+    type Output: Send + 'static;
+
+    // Does the next step, and returns the result once there are no steps left
+    fn step(&mut self) -> Option<Self::Output>;
+
+    fn progress(&self) -> TaskProgress;
+}
+
+#[derive(Clone)]
+pub struct TaskProgress {
+    // Between 0 and 1
+    pub fraction: f32,
+    pub text: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum TaskPacket<T> {
+    Progress(TaskProgress),
+    Done(T),
+}
+
+// Runs an optimizer without blocking the UI, it's stopped when the task is dropped.
+// Native builds run it on its own thread. The web build has no threads, so it runs a few steps every frame.
+pub struct OptimizerTask<O: Optimizer> {
+    progress: TaskProgress,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    channel_recv: mpsc::Receiver<TaskPacket<O::Output>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    cancelled: Arc<AtomicBool>,
+
+    #[cfg(target_arch = "wasm32")]
+    optimizer: O,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<O: Optimizer> OptimizerTask<O> {
+    pub fn new(mut optimizer: O) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let progress = optimizer.progress();
+
+        let thread_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            while !thread_cancelled.load(Ordering::Relaxed) {
+                let packet = match optimizer.step() {
+                    Some(output) => TaskPacket::Done(output),
+                    None => TaskPacket::Progress(optimizer.progress()),
+                };
+                let done = matches!(packet, TaskPacket::Done(_));
+
+                // The receiver is gone if the task was dropped
+                if tx.send(packet).is_err() || done {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            progress,
+            channel_recv: rx,
+            cancelled,
+        }
+    }
+
+    // `TryRecvError::Disconnected` means the optimizer panicked
+    pub fn try_get_result(&mut self) -> Result<O::Output, TryRecvError> {
+        loop {
+            match self.channel_recv.try_recv()? {
+                TaskPacket::Progress(progress) => self.progress = progress,
+                TaskPacket::Done(output) => return Ok(output),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<O: Optimizer> Drop for OptimizerTask<O> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<O: Optimizer> OptimizerTask<O> {
+    pub fn new(optimizer: O) -> Self {
+        Self {
+            progress: optimizer.progress(),
+            optimizer,
+        }
+    }
+
+    // Runs the optimizer for a frame, so it has to be called every frame until it's done
+    pub fn try_get_result(&mut self) -> Result<O::Output, TryRecvError> {
+        let start_time = Instant::now();
+        while start_time.elapsed() < FRAME_BUDGET {
+            if let Some(output) = self.optimizer.step() {
+                return Ok(output);
+            }
+        }
+        self.progress = self.optimizer.progress();
+
+        Err(TryRecvError::Empty)
+    }
+}
+
+impl<O: Optimizer> OptimizerTask<O> {
+    pub fn progress(&self) -> &TaskProgress {
+        &self.progress
+    }
+}
@@ -1,12 +1,99 @@
+use crate::ai::types::ai_settings::AISettings;
+use crate::ai::types::objective::Objective;
+use crate::ai::types::rotation_constraints::{RotationConstraints, SkillDeadline, SkillLimit};
 use crate::game::entities::armors::Armor;
 use crate::game::entities::enemies::Challenge;
+use crate::game::entities::entity::EntityMutRef::PlayerMutRef;
+use crate::game::entities::entity::EntityTrait;
 use crate::game::game_manager::GameManager;
 use crate::game::types::dict::Dict;
 use crate::game::types::gear::Slot;
 use crate::gui::gear::GearSet;
-use crate::gui::stats_handler::StatsHandler;
+use crate::gui::stats_handler::{StatsHandler, MAX_STAT, STATS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::thread;
 
-#[derive(Clone)]
+pub const MAX_LEVEL: i32 = 90;
+
+// The values allowed by the number inputs of the AI options
+pub const DEPTH_RANGE: RangeInclusive<u32> = 2..=10;
+pub const ALTERNATIVES_RANGE: RangeInclusive<u32> = 0..=14;
+pub const SAMPLES_RANGE: RangeInclusive<u32> = 1..=20;
+pub const THREADS_RANGE: RangeInclusive<u32> = 1..=64;
+pub const RECORD_NODES_RANGE: RangeInclusive<u32> = 0..=100000;
+pub const MIN_MP_RANGE: RangeInclusive<u32> = 0..=9999;
+pub const MAX_USES_RANGE: RangeInclusive<u32> = 0..=99;
+pub const DEADLINE_RANGE: RangeInclusive<u32> = 2..=99;
+
+// An option that wasn't set with its input, like a saved or shared one, with a value the input doesn't allow
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionsError {
+    Level(i32),
+    Number {
+        option: String,
+        value: String,
+        range: RangeInclusive<u32>,
+    },
+}
+
+impl Display for OptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionsError::Level(level) => {
+                write!(f, "Level {} is not between 1 and {}", level, MAX_LEVEL)
+            }
+            OptionsError::Number {
+                option,
+                value,
+                range,
+            } => write!(
+                f,
+                "{} is \"{}\", but must be a number between {} and {}",
+                option,
+                value,
+                range.start(),
+                range.end()
+            ),
+        }
+    }
+}
+
+fn parse_number(
+    option: &str,
+    value: &str,
+    range: RangeInclusive<u32>,
+) -> Result<u32, OptionsError> {
+    value
+        .parse()
+        .ok()
+        .filter(|number| range.contains(number))
+        .ok_or_else(|| OptionsError::Number {
+            option: option.to_string(),
+            value: value.to_string(),
+            range,
+        })
+}
+
+// Replaces `value` with `default` if it isn't a number in `range`
+fn sanitize_number(
+    option: &str,
+    value: &mut String,
+    default: &str,
+    range: RangeInclusive<u32>,
+    errors: &mut Vec<OptionsError>,
+) {
+    if let Err(error) = parse_number(option, value, range) {
+        errors.push(error);
+        *value = default.to_string();
+    }
+}
+
+// Missing fields are set to their defaults, so options added later can still load older saves
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct GameOptions {
     pub level: i32,
     pub stats: StatsHandler,
@@ -16,6 +103,41 @@ pub struct GameOptions {
 }
 
 impl GameOptions {
+    // Replaces the options the inputs don't allow with their defaults, and returns them
+    pub fn sanitize(&mut self) -> Vec<OptionsError> {
+        let mut errors = Vec::new();
+
+        if !(1..=MAX_LEVEL).contains(&self.level) {
+            errors.push(OptionsError::Level(self.level));
+            self.level = Self::default().level;
+        }
+
+        // Unknown stats are dropped
+        let mut stats = StatsHandler::new();
+        for stat in STATS {
+            let mut value = self.stats.map.get(stat).cloned().unwrap_or_default();
+            sanitize_number(stat, &mut value, "0", 0..=MAX_STAT, &mut errors);
+            stats.map.insert(stat.to_string(), value);
+        }
+        self.stats = stats;
+
+        errors
+    }
+
+    pub fn create_player(&self, gear_set: Option<&GearSet>) -> Box<dyn EntityTrait + Send> {
+        let mut player = self
+            .armor
+            .create(Dict::from(self.stats.clone()), self.level);
+
+        if let (Some(gear_set), PlayerMutRef(player)) = (gear_set, player.get_base_type_mut()) {
+            for item in gear_set.set.values() {
+                player.equip(item.base.clone());
+            }
+        }
+
+        player
+    }
+
     pub fn create_game(&self) -> GameManager {
         let player = self
             .armor
@@ -37,15 +159,125 @@ impl Default for GameOptions {
     }
 }
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct AIOptions {
     pub depth: String,
+    pub alternatives: String,
+    pub objective: Objective,
+    pub samples: String,
+    pub threads: String,
+    pub record_nodes: String,
+
+    // Rotation constraints, skills are stored by name
+    pub opener: Vec<String>,
+    pub forbidden_skills: Vec<String>,
+    pub min_mp: String,
+    pub max_uses: Vec<(String, String)>,
+    pub deadlines: Vec<(String, String)>,
+}
+
+impl AIOptions {
+    // Replaces the options the inputs don't allow with their defaults, and returns them.
+    // Constraints with invalid numbers are dropped.
+    pub fn sanitize(&mut self) -> Vec<OptionsError> {
+        let mut errors = Vec::new();
+
+        let default = Self::default();
+        for (option, value, default, range) in [
+            ("Depth", &mut self.depth, default.depth, DEPTH_RANGE),
+            (
+                "Alternatives",
+                &mut self.alternatives,
+                default.alternatives,
+                ALTERNATIVES_RANGE,
+            ),
+            ("Samples", &mut self.samples, default.samples, SAMPLES_RANGE),
+            ("Threads", &mut self.threads, default.threads, THREADS_RANGE),
+            (
+                "Recorded Nodes",
+                &mut self.record_nodes,
+                default.record_nodes,
+                RECORD_NODES_RANGE,
+            ),
+            ("Min MP", &mut self.min_mp, default.min_mp, MIN_MP_RANGE),
+        ] {
+            sanitize_number(option, value, &default, range, &mut errors);
+        }
+
+        for (rows, name, range) in [
+            (&mut self.max_uses, "Max Uses", MAX_USES_RANGE),
+            (&mut self.deadlines, "Use Before Turn", DEADLINE_RANGE),
+        ] {
+            rows.retain(|(skill, number)| {
+                let option = format!("{} of {}", name, skill);
+                match parse_number(&option, number, range.clone()) {
+                    Ok(_) => true,
+                    Err(error) => {
+                        errors.push(error);
+                        false
+                    }
+                }
+            });
+        }
+
+        errors
+    }
+
+    pub fn create_settings(&self) -> AISettings {
+        AISettings {
+            depth: self.depth.parse().unwrap(),
+            alternatives: self.alternatives.parse().unwrap(),
+            objective: self.objective,
+            samples: self.samples.parse().unwrap(),
+            threads: self.threads.parse().unwrap(),
+            record_nodes: self.record_nodes.parse().unwrap(),
+            constraints: self.create_constraints(),
+        }
+    }
+
+    fn create_constraints(&self) -> RotationConstraints {
+        RotationConstraints {
+            opener: self.opener.clone(),
+            forbidden: self.forbidden_skills.clone(),
+            min_mp: self.min_mp.parse().unwrap(),
+            max_uses: self
+                .max_uses
+                .iter()
+                .map(|(skill, uses)| SkillLimit {
+                    skill: skill.clone(),
+                    uses: uses.parse().unwrap(),
+                })
+                .collect(),
+            deadlines: self
+                .deadlines
+                .iter()
+                .map(|(skill, turn)| SkillDeadline {
+                    skill: skill.clone(),
+                    turn: turn.parse().unwrap(),
+                })
+                .collect(),
+        }
+    }
 }
 
 impl Default for AIOptions {
     fn default() -> Self {
         Self {
             depth: '4'.to_string(),
+            alternatives: '3'.to_string(),
+            objective: Objective::EnemyHealth,
+            samples: '3'.to_string(),
+            threads: thread::available_parallelism()
+                .map_or(1, |threads| threads.get() as u32)
+                .min(*THREADS_RANGE.end())
+                .to_string(),
+            record_nodes: "1000".to_string(),
+            opener: Vec::new(),
+            forbidden_skills: Vec::new(),
+            min_mp: '0'.to_string(),
+            max_uses: Vec::new(),
+            deadlines: Vec::new(),
         }
     }
 }
@@ -55,6 +287,9 @@ pub struct GearOptions {
     pub gear_sets: Vec<GearSet>,
     pub current_set_index: Option<usize>,
     pub slot: Slot,
+
+    // Pedia pages of the items the user has
+    pub owned_items: BTreeSet<u32>,
 }
 
 impl Default for GearOptions {
@@ -63,6 +298,7 @@ impl Default for GearOptions {
             gear_sets: Vec::new(),
             current_set_index: None,
             slot: Slot::Weapon,
+            owned_items: BTreeSet::new(),
         }
     }
 }
@@ -76,6 +312,20 @@ impl GearOptions {
         None
     }
 
+    // Adds the set and selects it, numbering its name if another set already has it
+    pub fn add_gear_set(&mut self, mut gear_set: GearSet) {
+        // Builds are told apart by their name
+        let name = gear_set.name.clone();
+        let mut copy = 1;
+        while self.gear_sets.contains(&gear_set) {
+            copy += 1;
+            gear_set.name = format!("{} ({})", name, copy);
+        }
+
+        self.gear_sets.push(gear_set);
+        self.current_set_index = Some(self.gear_sets.len() - 1);
+    }
+
     pub fn get_current_set_mut(&mut self) -> Option<&mut GearSet> {
         if let Some(index) = self.current_set_index {
             return self.gear_sets.get_mut(index);
@@ -84,3 +334,69 @@ impl GearOptions {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_resets_invalid_game_options() {
+        let mut game_options = GameOptions {
+            level: 120,
+            ..GameOptions::default()
+        };
+        game_options
+            .stats
+            .map
+            .insert("STR".to_string(), "x".to_string());
+        game_options
+            .stats
+            .map
+            .insert("LUK".to_string(), "201".to_string());
+        game_options
+            .stats
+            .map
+            .insert("DEX".to_string(), "150".to_string());
+        game_options.stats.map.remove("WIS");
+        game_options
+            .stats
+            .map
+            .insert("ABC".to_string(), "5".to_string());
+
+        assert_eq!(game_options.sanitize().len(), 4);
+        assert_eq!(game_options.level, 90);
+        assert_eq!(game_options.stats.get("STR"), 0);
+        assert_eq!(game_options.stats.get("LUK"), 0);
+        assert_eq!(game_options.stats.get("DEX"), 150);
+        assert_eq!(game_options.stats.map["WIS"], "0");
+        assert!(!game_options.stats.map.contains_key("ABC"));
+
+        assert!(game_options.sanitize().is_empty());
+    }
+
+    #[test]
+    fn sanitize_resets_invalid_ai_options() {
+        let mut ai_options = AIOptions {
+            depth: "x".to_string(),
+            threads: String::new(),
+            samples: "0".to_string(),
+            max_uses: vec![
+                ("Attack".to_string(), "3".to_string()),
+                ("Fury".to_string(), "-1".to_string()),
+            ],
+            deadlines: vec![("Attack".to_string(), "1".to_string())],
+            ..AIOptions::default()
+        };
+
+        assert_eq!(ai_options.sanitize().len(), 5);
+        assert_eq!(ai_options.depth, AIOptions::default().depth);
+        assert_eq!(ai_options.threads, AIOptions::default().threads);
+        assert_eq!(ai_options.samples, AIOptions::default().samples);
+        assert_eq!(ai_options.max_uses.len(), 1);
+        assert!(ai_options.deadlines.is_empty());
+
+        // The settings can be created without panicking
+        ai_options.create_settings();
+        assert!(ai_options.sanitize().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::game::types::gear::Slot;
+use crate::gui::gear::{GearSet, GuiItem};
+use crate::gui::options::{AIOptions, GameOptions, GearOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// Changing the saved format in a way that `#[serde(default)]` can't handle should bump this.
+// States saved by newer versions are ignored.
+const SAVE_VERSION: u64 = 1;
+
+const STORAGE_KEY: &str = "app_state";
+
+// A build, with its items stored by their pedia page so they follow updates to the item database
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedGearSet {
+    pub name: String,
+    pub items: BTreeMap<Slot, u32>,
+}
+
+impl From<&GearSet> for SavedGearSet {
+    fn from(set: &GearSet) -> Self {
+        Self {
+            name: set.name.clone(),
+            items: set
+                .set
+                .iter()
+                .map(|(slot, item)| (*slot, item.pedia_page_id))
+                .collect(),
+        }
+    }
+}
+
+impl SavedGearSet {
+    // Items that aren't in the database anymore are dropped
+    pub fn restore(&self, gear: &HashMap<Slot, Vec<GuiItem>>) -> GearSet {
+        let mut result = GearSet::new(self.name.clone());
+        for (slot, pedia_page_id) in &self.items {
+            let item = gear.get(slot).and_then(|items| {
+                items
+                    .iter()
+                    .find(|item| item.pedia_page_id == *pedia_page_id)
+            });
+            if let Some(item) = item {
+                result.set.insert(*slot, item.clone());
+            }
+        }
+
+        result
+    }
+}
+
+// Everything the user set up, saved between sessions
+#[derive(Serialize, Deserialize)]
+pub struct SavedState {
+    version: u64,
+
+    game_options: GameOptions,
+    ai_options: AIOptions,
+
+    gear_sets: Vec<SavedGearSet>,
+    current_set_index: Option<usize>,
+    slot: Slot,
+
+    #[serde(default)]
+    owned_items: BTreeSet<u32>,
+}
+
+impl SavedState {
+    pub fn new(
+        game_options: &GameOptions,
+        ai_options: &AIOptions,
+        gear_options: &GearOptions,
+    ) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            game_options: game_options.clone(),
+            ai_options: ai_options.clone(),
+            gear_sets: gear_options
+                .gear_sets
+                .iter()
+                .map(SavedGearSet::from)
+                .collect(),
+            current_set_index: gear_options.current_set_index,
+            slot: gear_options.slot,
+            owned_items: gear_options.owned_items.clone(),
+        }
+    }
+
+    // Returns `None` if nothing was saved, or the saved state can't be read.
+    // Saved options can be edited by hand, so the ones the inputs don't allow are reset to their defaults.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Option<Self> {
+        let data = storage?.get_string(STORAGE_KEY)?;
+        let mut state: SavedState = serde_json::from_str(&data).ok()?;
+
+        if state.version > SAVE_VERSION {
+            return None;
+        }
+
+        state.game_options.sanitize();
+        state.ai_options.sanitize();
+
+        Some(state)
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(STORAGE_KEY, serde_json::to_string(self).unwrap());
+    }
+
+    pub fn restore(
+        self,
+        gear: &HashMap<Slot, Vec<GuiItem>>,
+    ) -> (GameOptions, AIOptions, GearOptions) {
+        let gear_sets: Vec<GearSet> = self.gear_sets.iter().map(|set| set.restore(gear)).collect();
+        let current_set_index = self
+            .current_set_index
+            .filter(|index| *index < gear_sets.len());
+
+        (
+            self.game_options,
+            self.ai_options,
+            GearOptions {
+                gear_sets,
+                current_set_index,
+                slot: self.slot,
+                owned_items: self.owned_items,
+            },
+        )
+    }
+}
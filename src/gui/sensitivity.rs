@@ -0,0 +1,201 @@
+use crate::game::entities::entity::Entity;
+use crate::game::types::dict::Dict;
+use crate::gui::gear::GearSet;
+use crate::gui::options::GameOptions;
+use crate::gui::stats_handler::STATS;
+use crate::gui::utils;
+use egui::{Color32, Ui};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+// Crit, boost, bonus, BPD and MPM
+const BONUSES: [&str; 9] = [
+    "crit",
+    "boost",
+    "bonus",
+    "block",
+    "parry",
+    "dodge",
+    "melee_def",
+    "pierce_def",
+    "magic_def",
+];
+
+// One more point of a stat, bonus or resist.
+// Bonuses and resists are added as gear, so points over the gear caps don't change anything.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Perturbation {
+    Stat(String),
+    Bonus(String),
+    Resist(String),
+}
+
+impl Perturbation {
+    fn apply(&self, entity: &mut Entity) {
+        match self {
+            Perturbation::Stat(stat) => {
+                let diff = Dict::from([(stat.as_str(), 1.0)]);
+                entity.recalculate_stat_bonuses(&diff);
+                entity.bonuses.merge(&diff);
+            }
+            Perturbation::Bonus(bonus) => entity.gear_bonuses.add(bonus, 1.0),
+            Perturbation::Resist(elem) => entity.gear_resists.add(elem, 1.0),
+        }
+    }
+}
+
+impl Display for Perturbation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Perturbation::Stat(stat) => write!(f, "+1 {}", stat),
+            Perturbation::Bonus(bonus) => write!(f, "+1 {}", utils::to_title_case(bonus)),
+            Perturbation::Resist(elem) => {
+                write!(f, "+1% {} Resist", utils::to_title_case(elem))
+            }
+        }
+    }
+}
+
+// Expected values of a single basic attack of the player, and of every enemy.
+// Skills, DoTs and armor effects aren't simulated, so this doesn't rank stats for a whole rotation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outcome {
+    // Against the first enemy
+    pub attack_damage: f32,
+    pub damage_taken: f32,
+    pub max_hp: i32,
+}
+
+impl Outcome {
+    fn new(player: &Entity, enemies: &[Entity]) -> Self {
+        Self {
+            attack_damage: player.clone().expected_attack_damage(&enemies[0]),
+            damage_taken: enemies
+                .iter()
+                .map(|enemy| enemy.clone().expected_attack_damage(player))
+                .sum(),
+            max_hp: player.max_hp,
+        }
+    }
+
+    // The number of enemy basic attacks the player survives, `None` if they don't deal damage
+    pub fn turns_survived(&self) -> Option<f32> {
+        (self.damage_taken > 0.0).then(|| self.max_hp as f32 / self.damage_taken)
+    }
+}
+
+pub struct Sensitivity {
+    pub perturbation: Perturbation,
+    pub outcome: Outcome,
+}
+
+// How much one more point of every stat, bonus and resist changes the basic attacks of the current setup
+pub struct SensitivityReport {
+    pub base: Outcome,
+    pub rows: Vec<Sensitivity>,
+}
+
+impl SensitivityReport {
+    pub fn new(game_options: &GameOptions, gear_set: Option<&GearSet>) -> Self {
+        let player = game_options.create_player(gear_set);
+        let player = player.get_base_entity();
+        let enemies: Vec<Entity> = game_options
+            .challenge
+            .create(game_options.level)
+            .iter()
+            .map(|enemy| enemy.get_base_entity().clone())
+            .collect();
+
+        // The elements of the enemies, and every resist the player already has
+        let resists: BTreeSet<String> = enemies
+            .iter()
+            .map(|enemy| enemy.elem.clone())
+            .chain(["all".to_string()])
+            .chain(player.resists.keys().cloned())
+            .chain(player.gear_resists.keys().cloned())
+            .collect();
+
+        let perturbations = STATS
+            .iter()
+            .map(|stat| Perturbation::Stat(stat.to_string()))
+            .chain(
+                BONUSES
+                    .iter()
+                    .map(|bonus| Perturbation::Bonus(bonus.to_string())),
+            )
+            .chain(resists.into_iter().map(Perturbation::Resist));
+
+        let rows = perturbations
+            .map(|perturbation| {
+                let mut new_player = player.clone();
+                perturbation.apply(&mut new_player);
+
+                Sensitivity {
+                    outcome: Outcome::new(&new_player, &enemies),
+                    perturbation,
+                }
+            })
+            .collect();
+
+        Self {
+            base: Outcome::new(player, &enemies),
+            rows,
+        }
+    }
+
+    pub fn draw(&self, ui: &mut Ui) {
+        egui::Grid::new("sensitivity_report_grid")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Attack Damage");
+                ui.label("Damage Taken");
+                ui.label("Max HP");
+                ui.label("Attacks Survived");
+                ui.end_row();
+
+                ui.label("Current");
+                ui.label(format!("{:.2}", self.base.attack_damage));
+                ui.label(format!("{:.2}", self.base.damage_taken));
+                ui.label(self.base.max_hp.to_string());
+                ui.label(
+                    self.base
+                        .turns_survived()
+                        .map_or("-".to_string(), |turns| format!("{:.2}", turns)),
+                );
+                ui.end_row();
+
+                for row in &self.rows {
+                    ui.label(row.perturbation.to_string());
+                    Self::draw_delta(
+                        ui,
+                        row.outcome.attack_damage - self.base.attack_damage,
+                        false,
+                    );
+                    Self::draw_delta(ui, row.outcome.damage_taken - self.base.damage_taken, true);
+                    Self::draw_delta(ui, (row.outcome.max_hp - self.base.max_hp) as f32, false);
+                    match (row.outcome.turns_survived(), self.base.turns_survived()) {
+                        (Some(turns), Some(base_turns)) => {
+                            Self::draw_delta(ui, turns - base_turns, false)
+                        }
+                        _ => {
+                            ui.label("-");
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn draw_delta(ui: &mut Ui, delta: f32, lower_is_better: bool) {
+        let text = format!("{:+.3}", delta);
+        if delta.abs() < 0.0005 {
+            ui.label(text);
+        } else if (delta > 0.0) != lower_is_better {
+            ui.colored_label(Color32::GREEN, text);
+        } else {
+            ui.colored_label(Color32::RED, text);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use crate::game::entities::entity::Entity;
+use crate::game::fight_record::{FightRecord, FightSummary, MAX_TURNS};
+use crate::game::game_manager::GameManager;
+use crate::game::rotation::Rotation;
+use crate::gui::gear::GearSet;
+use crate::gui::optimizer_task::{Optimizer, TaskProgress};
+use crate::gui::options::GameOptions;
+use crate::gui::stats_handler::{StatsHandler, MAX_STAT, STATS};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+// The most points a bonus derived from a stat needs to change, 20 LUK for every point of BPD and MPM
+const MAX_FLOORED_STEP: u32 = 20;
+
+// Stops the search if moving points around keeps improving the score for this long
+const MAX_MOVES: usize = 100;
+
+// Win rates are measured over random fights, so they're only compared when they differ by more
+// than this many standard errors. Closer win rates are compared by damage instead.
+const SIGNIFICANCE: f64 = 2.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StatObjective {
+    // Expected damage of a basic attack against the first enemy
+    AttackDamage,
+
+    // Win rate of a rotation over several fights, close win rates are compared by the damage per turn of the fights
+    WinRate,
+}
+
+impl StatObjective {
+    pub fn vec() -> Vec<StatObjective> {
+        vec![StatObjective::AttackDamage, StatObjective::WinRate]
+    }
+}
+
+impl Display for StatObjective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                StatObjective::AttackDamage => "Basic Attack Damage",
+                StatObjective::WinRate => "Win Rate",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct StatOptimizerSettings {
+    pub objective: StatObjective,
+
+    // Points are added and moved in steps of this size
+    pub step: u32,
+
+    // Only used by `StatObjective::WinRate`
+    pub rotation: Option<Rotation>,
+    pub fights: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StatScore {
+    // Always 0 with `StatObjective::AttackDamage`
+    pub win_rate: f64,
+    // The number of fights the win rate was measured over
+    pub fights: usize,
+
+    // Damage per turn of the fights with `StatObjective::WinRate`,
+    // otherwise the expected damage of a basic attack
+    pub damage: f64,
+}
+
+impl StatScore {
+    fn compare(&self, other: &StatScore) -> Ordering {
+        let standard_error = (self.variance() + other.variance()).sqrt();
+        if (self.win_rate - other.win_rate).abs() > SIGNIFICANCE * standard_error {
+            self.win_rate.total_cmp(&other.win_rate)
+        } else {
+            self.damage.total_cmp(&other.damage)
+        }
+    }
+
+    // Variance of the measured win rate
+    fn variance(&self) -> f64 {
+        if self.fights == 0 {
+            return 0.0;
+        }
+        self.win_rate * (1.0 - self.win_rate) / self.fights as f64
+    }
+}
+
+pub struct OptimizedStats {
+    pub objective: StatObjective,
+    pub stats: StatsHandler,
+    pub score: StatScore,
+
+    // Points that didn't improve the score
+    pub unspent: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Phase {
+    // Adding `amount` points to a single stat
+    Adding { amount: u32 },
+    // Moving a step from one stat to another, after `moves` moves were made
+    Moving { moves: usize },
+    Done,
+}
+
+// Spends the level's stat points one step at a time on the stat that improves the score the most,
+// then moves steps between stats while that improves the score.
+// Every step of the optimizer scores one allocation of the current round.
+pub struct StatOptimizer {
+    game_options: GameOptions,
+    gear_set: Option<GearSet>,
+    settings: StatOptimizerSettings,
+    enemy: Entity,
+
+    budget: u32,
+    stats: StatsHandler,
+    score: StatScore,
+    unspent: u32,
+
+    phase: Phase,
+    // The allocations of the round that are left to score, with the points they spend
+    candidates: Vec<(StatsHandler, u32)>,
+    round_size: usize,
+    best: Option<(StatsHandler, StatScore, u32)>,
+}
+
+impl StatOptimizer {
+    pub fn new(
+        game_options: GameOptions,
+        gear_set: Option<GearSet>,
+        mut settings: StatOptimizerSettings,
+    ) -> Self {
+        settings.step = settings.step.max(1);
+        let enemies = game_options.challenge.create(game_options.level);
+        let budget = StatsHandler::budget(game_options.level);
+
+        let mut optimizer = Self {
+            enemy: enemies[0].get_base_entity().clone(),
+            game_options,
+            gear_set,
+            phase: Phase::Adding {
+                amount: settings.step,
+            },
+            settings,
+            budget,
+            stats: StatsHandler::new(),
+            score: StatScore {
+                win_rate: 0.0,
+                fights: 0,
+                damage: 0.0,
+            },
+            unspent: budget,
+            candidates: Vec::new(),
+            round_size: 0,
+            best: None,
+        };
+        optimizer.score = optimizer.score(&optimizer.stats);
+        optimizer.start_round();
+
+        optimizer
+    }
+
+    fn start_round(&mut self) {
+        let stats = &self.stats;
+        let step = self.settings.step;
+
+        self.candidates = match self.phase {
+            // Some bonuses only change every few points, so bigger amounts are tried when a single step doesn't help
+            Phase::Adding { amount } if amount <= self.unspent => STATS
+                .iter()
+                .filter(|stat| stats.get(stat) + amount <= MAX_STAT)
+                .map(|stat| {
+                    let mut new_stats = stats.clone();
+                    new_stats.set(stat, stats.get(stat) + amount);
+                    (new_stats, amount)
+                })
+                .collect(),
+            Phase::Moving { .. } => STATS
+                .iter()
+                .filter(|from| stats.get(from) >= step)
+                .flat_map(|from| {
+                    STATS
+                        .iter()
+                        .filter(move |to| from != *to && stats.get(to) + step <= MAX_STAT)
+                        .map(move |to| {
+                            let mut new_stats = stats.clone();
+                            new_stats.set(from, stats.get(from) - step);
+                            new_stats.set(to, stats.get(to) + step);
+                            (new_stats, 0)
+                        })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.round_size = self.candidates.len();
+        self.best = None;
+    }
+
+    // Keeps the best allocation of the round if it improves the score, and picks the next round
+    fn finish_round(&mut self) {
+        let improved = match self.best.take() {
+            Some((stats, score, spent)) if score.compare(&self.score).is_gt() => {
+                self.stats = stats;
+                self.score = score;
+                self.unspent -= spent;
+                true
+            }
+            _ => false,
+        };
+
+        let step = self.settings.step;
+        self.phase = match self.phase {
+            Phase::Adding { .. } if improved => Phase::Adding { amount: step },
+            Phase::Adding { amount }
+                if amount + step <= self.unspent && amount + step <= MAX_FLOORED_STEP.max(step) =>
+            {
+                Phase::Adding {
+                    amount: amount + step,
+                }
+            }
+            Phase::Adding { .. } => Phase::Moving { moves: 0 },
+            Phase::Moving { moves } if improved && moves + 1 < MAX_MOVES => {
+                Phase::Moving { moves: moves + 1 }
+            }
+            _ => Phase::Done,
+        };
+        self.start_round();
+    }
+
+    fn score(&self, stats: &StatsHandler) -> StatScore {
+        let mut game_options = self.game_options.clone();
+        game_options.stats = stats.clone();
+        let mut player = game_options.create_player(self.gear_set.as_ref());
+
+        match (self.settings.objective, &self.settings.rotation) {
+            (StatObjective::WinRate, Some(rotation)) => {
+                let game =
+                    GameManager::new(player, game_options.challenge.create(game_options.level));
+                let records: Vec<FightRecord> = (0..self.settings.fights)
+                    .map(|_| rotation.play_fight(game.clone(), MAX_TURNS))
+                    .collect();
+                let summary = FightSummary::from(records.as_slice());
+
+                StatScore {
+                    win_rate: summary.win_rate,
+                    fights: self.settings.fights,
+                    damage: summary.damage_per_turn,
+                }
+            }
+            _ => StatScore {
+                win_rate: 0.0,
+                fights: 0,
+                damage: player
+                    .get_base_entity_mut()
+                    .expected_attack_damage(&self.enemy) as f64,
+            },
+        }
+    }
+}
+
+impl Optimizer for StatOptimizer {
+    type Output = OptimizedStats;
+
+    fn step(&mut self) -> Option<OptimizedStats> {
+        if self.phase == Phase::Done {
+            return Some(OptimizedStats {
+                objective: self.settings.objective,
+                stats: self.stats.clone(),
+                score: self.score,
+                unspent: self.unspent,
+            });
+        }
+
+        let Some((stats, spent)) = self.candidates.pop() else {
+            self.finish_round();
+            return None;
+        };
+        let score = self.score(&stats);
+        let is_better = match &self.best {
+            Some((_, best_score, _)) => score.compare(best_score).is_gt(),
+            None => true,
+        };
+        if is_better {
+            self.best = Some((stats, score, spent));
+        }
+
+        None
+    }
+
+    fn progress(&self) -> TaskProgress {
+        match self.phase {
+            Phase::Moving { moves } => TaskProgress {
+                fraction: (self.round_size - self.candidates.len()) as f32
+                    / self.round_size.max(1) as f32,
+                text: format!("Moving points, {} moves so far", moves),
+            },
+            _ => {
+                let spent = self.budget - self.unspent;
+                TaskProgress {
+                    fraction: spent as f32 / self.budget.max(1) as f32,
+                    text: format!("Spending points {} / {}", spent, self.budget),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(win_rate: f64, fights: usize, damage: f64) -> StatScore {
+        StatScore {
+            win_rate,
+            fights,
+            damage,
+        }
+    }
+
+    #[test]
+    fn win_rates_within_the_noise_are_compared_by_damage() {
+        // 2 wins out of 20 fights could easily be luck
+        let lucky = score(0.9, 20, 10.0);
+        let strong = score(0.8, 20, 12.0);
+        assert!(strong.compare(&lucky).is_gt());
+
+        // But not out of 1000
+        let lucky = score(0.9, 1000, 10.0);
+        let strong = score(0.8, 1000, 12.0);
+        assert!(lucky.compare(&strong).is_gt());
+    }
+
+    #[test]
+    fn attack_damage_spends_every_useful_point() {
+        let game_options = GameOptions::default();
+        let settings = StatOptimizerSettings {
+            objective: StatObjective::AttackDamage,
+            step: 10,
+            rotation: None,
+            fights: 1,
+        };
+        let mut optimizer = StatOptimizer::new(game_options.clone(), None, settings);
+        let empty_score = optimizer.score;
+
+        let result = loop {
+            if let Some(result) = optimizer.step() {
+                break result;
+            }
+        };
+
+        assert!(result.score.compare(&empty_score).is_gt());
+        assert_eq!(
+            result.stats.total() + result.unspent,
+            StatsHandler::budget(game_options.level)
+        );
+        assert!(result.stats.validate(game_options.level).is_ok());
+    }
+}
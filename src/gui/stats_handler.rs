@@ -1,7 +1,37 @@
 use crate::game::types::dict::Dict;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Clone)]
+pub const STATS: [&str; 7] = ["STR", "DEX", "INT", "CHA", "LUK", "END", "WIS"];
+
+pub const MAX_STAT: u32 = 200;
+
+// Stat points gained on every level up
+const STAT_POINTS_PER_LEVEL: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatsError {
+    OverBudget { total: u32, budget: u32 },
+    OverCap { stat: String, value: u32 },
+}
+
+impl Display for StatsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsError::OverBudget { total, budget } => write!(
+                f,
+                "{} stat points are used, but the level only gives {}",
+                total, budget
+            ),
+            StatsError::OverCap { stat, value } => {
+                write!(f, "{} is {}, but can be at most {}", stat, value, MAX_STAT)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct StatsHandler {
     pub map: HashMap<String, String>,
 }
@@ -20,6 +50,45 @@ impl StatsHandler {
             ]),
         }
     }
+
+    // The stat points a player of `level` has
+    pub fn budget(level: i32) -> u32 {
+        (level.max(1) - 1) as u32 * STAT_POINTS_PER_LEVEL
+    }
+
+    pub fn get(&self, stat: &str) -> u32 {
+        self.map
+            .get(stat)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn set(&mut self, stat: &str, value: u32) {
+        self.map.insert(stat.to_string(), value.to_string());
+    }
+
+    pub fn total(&self) -> u32 {
+        STATS.iter().map(|stat| self.get(stat)).sum()
+    }
+
+    pub fn validate(&self, level: i32) -> Result<(), StatsError> {
+        for stat in STATS {
+            let value = self.get(stat);
+            if value > MAX_STAT {
+                return Err(StatsError::OverCap {
+                    stat: stat.to_string(),
+                    value,
+                });
+            }
+        }
+
+        let (total, budget) = (self.total(), Self::budget(level));
+        if total > budget {
+            return Err(StatsError::OverBudget { total, budget });
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for StatsHandler {
@@ -28,16 +97,14 @@ impl Default for StatsHandler {
     }
 }
 
+// Stats that aren't numbers yet, like text that is still being typed, are 0
 impl From<StatsHandler> for Dict {
     fn from(value: StatsHandler) -> Self {
-        Dict::from([
-            ("STR", value.map["STR"].parse().unwrap()),
-            ("DEX", value.map["DEX"].parse().unwrap()),
-            ("INT", value.map["INT"].parse().unwrap()),
-            ("CHA", value.map["CHA"].parse().unwrap()),
-            ("LUK", value.map["LUK"].parse().unwrap()),
-            ("END", value.map["END"].parse().unwrap()),
-            ("WIS", value.map["WIS"].parse().unwrap()),
-        ])
+        let mut result = Dict::new();
+        for stat in STATS {
+            result.set(stat, value.get(stat) as f32);
+        }
+
+        result
     }
 }
@@ -0,0 +1,106 @@
+use crate::game::types::gear::Slot;
+use crate::gui::gear::GuiItem;
+use crate::gui::options::{AIOptions, GameOptions, GearOptions, OptionsError};
+use crate::gui::saved_state::SavedGearSet;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Changing the format should bump this, links made by newer versions are ignored
+const URL_STATE_VERSION: u64 = 1;
+
+// The setup of the web app, kept in the URL fragment so a link opens the same setup
+#[derive(Serialize, Deserialize)]
+pub struct UrlState {
+    version: u64,
+    game_options: GameOptions,
+    ai_options: AIOptions,
+
+    // The selected build
+    gear_set: Option<SavedGearSet>,
+}
+
+impl UrlState {
+    pub fn new(
+        game_options: &GameOptions,
+        ai_options: &AIOptions,
+        gear_options: &GearOptions,
+    ) -> Self {
+        Self {
+            version: URL_STATE_VERSION,
+            game_options: game_options.clone(),
+            ai_options: ai_options.clone(),
+            gear_set: gear_options.get_current_set().map(SavedGearSet::from),
+        }
+    }
+
+    // The compact JSON in URL safe base64
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_string(self).unwrap())
+    }
+
+    pub fn decode(fragment: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(fragment).ok()?;
+        let state: UrlState = serde_json::from_slice(&bytes).ok()?;
+
+        if state.version > URL_STATE_VERSION {
+            return None;
+        }
+
+        Some(state)
+    }
+
+    // Links can be edited by hand, so their options are checked before they replace the saved ones
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        let mut errors = self.game_options.clone().sanitize();
+        errors.extend(self.ai_options.clone().sanitize());
+
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    // Replaces the options with the link's, and selects its build
+    pub fn restore(
+        self,
+        gear: &HashMap<Slot, Vec<GuiItem>>,
+        (_, _, mut gear_options): (GameOptions, AIOptions, GearOptions),
+    ) -> (GameOptions, AIOptions, GearOptions) {
+        if let Some(gear_set) = self.gear_set {
+            // Opening the same link twice shouldn't add the build twice
+            let existing_set = gear_options
+                .gear_sets
+                .iter()
+                .position(|set| SavedGearSet::from(set) == gear_set);
+
+            match existing_set {
+                Some(index) => gear_options.current_set_index = Some(index),
+                None => gear_options.add_gear_set(gear_set.restore(gear)),
+            }
+        }
+
+        (self.game_options, self.ai_options, gear_options)
+    }
+
+    // Reads the state from the page's URL, if it has one
+    pub fn read() -> Option<Self> {
+        let hash = web_sys::window()?.location().hash().ok()?;
+
+        Self::decode(hash.strip_prefix('#').unwrap_or(&hash))
+    }
+
+    // Replaces the page's URL fragment with an encoded state,
+    // without adding an entry to the browser's history
+    pub fn write(fragment: &str) {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+
+        if let Ok(history) = window.history() {
+            let url = format!("#{}", fragment);
+            let _ = history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url));
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ai::types::eval_value::EvalValue;
 use crate::game::types::dict::Dict;
 use egui::Ui;
 
@@ -20,7 +21,26 @@ pub fn draw_dict(ui: &mut Ui, dict: &Dict, id_str: String) {
         });
 }
 
-fn to_title_case(key: &str) -> String {
+pub fn eval_string(eval: &EvalValue, show_win_probability: bool) -> String {
+    if show_win_probability {
+        win_probability_string(eval)
+    } else {
+        eval.to_string()
+    }
+}
+
+// Only undecided sampled evaluations have measured win rates, the other scores are heuristic estimates
+pub fn win_probability_string(eval: &EvalValue) -> String {
+    match eval {
+        EvalValue::Won { .. } => "Won".to_string(),
+        EvalValue::Lost => "Lost".to_string(),
+        EvalValue::MissedDeadline { .. } => "Missed deadline".to_string(),
+        EvalValue::InProgress { score, .. } => format!("~{:.1}% (heuristic)", 100.0 * score),
+        EvalValue::Sampled { .. } => eval.to_string(),
+    }
+}
+
+pub fn to_title_case(key: &str) -> String {
     let chars = key.chars();
     let mut result = String::new();
     let mut start_of_word = true;
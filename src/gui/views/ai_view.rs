@@ -1,25 +1,44 @@
-use crate::ai::ai_communication::{AICommunicator, AI};
+use crate::ai::ai_communication::{AICommunicator, AITurn, AI};
+use crate::ai::rotation_cache::SharedRotationCache;
+use crate::ai::types::eval_value::EvalValue;
+use crate::ai::types::objective::Objective;
+use crate::ai::types::search_progress::SearchProgress;
+use crate::ai::types::search_tree::SearchNode;
+use crate::ai::types::skill_eval::SkillEval;
 use crate::game::game_manager::GameStatus;
 use crate::gui::options::{AIOptions, GameOptions};
-use egui::Ui;
+use crate::gui::utils;
+use egui::{Color32, Ui};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct AIView {
     ai: Option<AICommunicator>,
     current_rotation: String,
+    last_turn: Option<AITurn>,
+
+    // Whether the current run estimates win probabilities
+    show_win_probability: bool,
 
     game_options: Rc<RefCell<GameOptions>>,
     ai_options: Rc<RefCell<AIOptions>>,
+    rotation_cache: SharedRotationCache,
 }
 
 impl AIView {
-    pub fn new(game_options: Rc<RefCell<GameOptions>>, ai_options: Rc<RefCell<AIOptions>>) -> Self {
+    pub fn new(
+        game_options: Rc<RefCell<GameOptions>>,
+        ai_options: Rc<RefCell<AIOptions>>,
+        rotation_cache: SharedRotationCache,
+    ) -> Self {
         Self {
             ai: None,
             current_rotation: String::new(),
+            last_turn: None,
+            show_win_probability: false,
             game_options,
             ai_options,
+            rotation_cache,
         }
     }
 
@@ -29,8 +48,25 @@ impl AIView {
         }
 
         if let Some(ai) = &mut self.ai {
-            if let Ok(skill) = ai.try_get_skill() {
-                self.current_rotation.push_str(&format!("{} ➡ ", skill));
+            // Results arrive from other threads, so keep polling while the AI is running
+            ui.ctx().request_repaint();
+
+            if let Some(progress) = ai.progress() {
+                Self::draw_progress(ui, &progress, self.show_win_probability);
+            }
+
+            if let Ok(turn) = ai.try_get_skill() {
+                if self.show_win_probability {
+                    self.current_rotation.push_str(&format!(
+                        "{} ({}) ➡ ",
+                        turn.skill_name,
+                        utils::win_probability_string(&turn.plan.eval)
+                    ));
+                } else {
+                    self.current_rotation
+                        .push_str(&format!("{} ➡ ", turn.skill_name));
+                }
+                self.last_turn = Some(turn);
 
                 match ai.ai.game.get_status() {
                     GameStatus::Won => {
@@ -48,13 +84,271 @@ impl AIView {
                     }
                 }
             }
-        } else if ui.button("Start").clicked() {
-            self.current_rotation.clear();
-            self.ai = Some(AICommunicator::new(AI::new(
-                self.game_options.borrow().create_game(),
-                self.ai_options.borrow().depth.parse().unwrap(),
-            )));
-            self.ai.as_mut().unwrap().do_turn();
+        } else {
+            ui.horizontal(|ui| {
+                if ui.button("Start").clicked() {
+                    self.start();
+                }
+
+                let cache = self.rotation_cache.borrow();
+                let clear_button =
+                    egui::Button::new(format!("Clear Cache ({} turns)", cache.len()));
+                let enabled = !cache.is_empty();
+                drop(cache);
+
+                if ui.add_enabled(enabled, clear_button).clicked() {
+                    self.rotation_cache.borrow_mut().clear();
+                }
+            });
+        }
+
+        if let Some(turn) = &self.last_turn {
+            ui.separator();
+            Self::draw_turn(ui, turn, self.show_win_probability);
+        }
+    }
+
+    fn start(&mut self) {
+        self.current_rotation.clear();
+        self.last_turn = None;
+
+        let settings = self.ai_options.borrow().create_settings();
+        self.show_win_probability = settings.objective == Objective::WinProbability;
+        self.ai = Some(AICommunicator::new(
+            AI::new(self.game_options.borrow().create_game(), settings),
+            Some(self.rotation_cache.clone()),
+        ));
+        self.ai.as_mut().unwrap().do_turn();
+    }
+
+    fn draw_progress(ui: &mut Ui, progress: &SearchProgress, show_win_probability: bool) {
+        ui.group(|ui| {
+            ui.heading("Searching...");
+            egui::Grid::new("ai_view_progress_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Depth");
+                    ui.label(format!("{} / {}", progress.depth, progress.max_depth));
+                    ui.end_row();
+
+                    ui.label("Nodes at this depth");
+                    ui.label(progress.nodes.to_string());
+                    ui.end_row();
+
+                    ui.label("Elapsed");
+                    ui.label(format!("{:.1}s", progress.elapsed.as_secs_f64()));
+                    ui.end_row();
+
+                    ui.label("Best so far");
+                    match &progress.best_skill {
+                        Some((name, eval)) if show_win_probability => ui.label(format!(
+                            "{} ({})",
+                            name,
+                            utils::win_probability_string(eval)
+                        )),
+                        Some((name, eval)) => ui.label(format!("{} ({})", name, eval)),
+                        None => ui.label("-"),
+                    };
+                    ui.end_row();
+                });
+
+            ui.add(
+                egui::ProgressBar::new(
+                    progress.skills_done as f32 / progress.skills_total.max(1) as f32,
+                )
+                .text(format!(
+                    "{} / {} skills",
+                    progress.skills_done, progress.skills_total
+                )),
+            );
+        });
+    }
+
+    fn draw_turn(ui: &mut Ui, turn: &AITurn, show_win_probability: bool) {
+        let stats = format!(
+            "Searched {} nodes at full depth in {:.2}s ({:.0} nodes/s)",
+            turn.stats.nodes,
+            turn.stats.elapsed.as_secs_f64(),
+            turn.stats.nodes_per_second()
+        );
+        if turn.cached {
+            ui.label(format!("Loaded from the cache ({})", stats.to_lowercase()));
+        } else {
+            ui.label(stats);
+        }
+        for constraint in &turn.unmet_constraints {
+            ui.colored_label(Color32::YELLOW, format!("Unmet constraint: {}", constraint));
+        }
+
+        ui.heading(format!("Planned next {} skills", turn.plan.line.len()));
+        egui::Grid::new("ai_view_plan_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Turn");
+                ui.label("Skill");
+                ui.label("Enemy HP");
+                ui.label("Player HP");
+                ui.label("Player MP");
+                if show_win_probability {
+                    ui.label("Win Chance");
+                }
+                ui.end_row();
+
+                for planned_skill in &turn.plan.line {
+                    let state = &planned_skill.state;
+                    ui.label((state.turn - 1).to_string());
+                    ui.label(&planned_skill.name);
+                    ui.label(format!("{:.1}%", state.enemies_hp_percent()));
+                    ui.label(format!("{} / {}", state.player.hp, state.player.max_hp));
+                    ui.label(format!("{} / {}", state.player.mp, state.player.max_mp));
+                    if show_win_probability {
+                        ui.label(utils::win_probability_string(&planned_skill.eval));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+
+        ui.collapsing(format!("Why {}?", turn.skill_name), |ui| {
+            Self::draw_explanation(ui, turn, show_win_probability);
+        });
+
+        if let Some(tree) = &turn.tree {
+            ui.collapsing(format!("Search Tree ({} nodes)", tree.size()), |ui| {
+                if ui.button("Copy as JSON").clicked() {
+                    let json = serde_json::to_string_pretty(tree).unwrap();
+                    ui.output_mut(|output| output.copied_text = json);
+                }
+
+                Self::draw_search_node(ui, tree, "ai_view_search_tree", show_win_probability);
+            });
+        }
+
+        ui.separator();
+
+        ui.heading("Alternatives");
+        if turn.alternatives.is_empty() {
+            ui.label("No alternatives were considered");
+            return;
+        }
+
+        egui::Grid::new("ai_view_alternatives_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Skill");
+                ui.label("Eval");
+                ui.label("Difference");
+                ui.label("Line");
+                ui.end_row();
+
+                for alternative in [&turn.plan].into_iter().chain(&turn.alternatives) {
+                    ui.label(&alternative.line[0].name);
+                    ui.label(utils::eval_string(&alternative.eval, show_win_probability));
+                    ui.label(Self::eval_difference(&alternative.eval, &turn.plan.eval));
+                    ui.label(Self::line_string(alternative));
+                    ui.end_row();
+                }
+            });
+    }
+
+    // The predicted result of every first skill, compared to the state it was picked in
+    fn draw_explanation(ui: &mut Ui, turn: &AITurn, show_win_probability: bool) {
+        let before = &turn.state;
+
+        egui::Grid::new("ai_view_explanation_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Skill");
+                ui.label("Eval");
+                ui.label("Enemy HP");
+                ui.label("Player HP");
+                ui.label("Player MP");
+                ui.label("New Effects");
+                ui.label("Follow-up");
+                ui.end_row();
+
+                for skill_eval in &turn.skill_evals {
+                    let after = &skill_eval.line[0].state;
+
+                    ui.label(&skill_eval.line[0].name);
+                    ui.label(utils::eval_string(&skill_eval.eval, show_win_probability));
+                    ui.label(format!(
+                        "{:+.1}%",
+                        after.enemies_hp_percent() - before.enemies_hp_percent()
+                    ));
+                    ui.label(format!("{:+}", after.player.hp - before.player.hp));
+                    ui.label(format!("{:+}", after.player.mp - before.player.mp));
+                    ui.label(after.new_effects(before).join(", "));
+                    ui.label(
+                        skill_eval.line[1..]
+                            .iter()
+                            .map(|planned_skill| planned_skill.name.clone())
+                            .collect::<Vec<String>>()
+                            .join(" ➡ "),
+                    );
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn draw_search_node(ui: &mut Ui, node: &SearchNode, id: &str, show_win_probability: bool) {
+        let state = &node.state;
+        ui.label(format!(
+            "Enemy HP: {:.1}%, Player HP: {} / {}, Player MP: {} / {}",
+            state.enemies_hp_percent(),
+            state.player.hp,
+            state.player.max_hp,
+            state.player.mp,
+            state.player.max_mp
+        ));
+        for entity in [&state.player].into_iter().chain(&state.enemies) {
+            if !entity.effects.is_empty() {
+                ui.label(format!(
+                    "{} effects: {}",
+                    entity.name,
+                    entity.effects.join(", ")
+                ));
+            }
         }
+
+        for (index, child) in node.children.iter().enumerate() {
+            let child_id = format!("{}_{}", id, index);
+            egui::CollapsingHeader::new(format!(
+                "{} ({})",
+                child.skill.as_deref().unwrap_or("-"),
+                utils::eval_string(&child.eval, show_win_probability)
+            ))
+            .id_source(&child_id)
+            .show(ui, |ui| {
+                Self::draw_search_node(ui, child, &child_id, show_win_probability);
+            });
+        }
+    }
+
+    fn eval_difference(eval: &EvalValue, best: &EvalValue) -> String {
+        match (eval, best) {
+            (
+                EvalValue::Won { turn, .. },
+                EvalValue::Won {
+                    turn: best_turn, ..
+                },
+            ) => format!("{:+} turns", turn - best_turn),
+            _ if eval == best => "-".to_string(),
+            _ => match (eval.score(), best.score()) {
+                (Some(score), Some(best_score)) => format!("{:.2}", score - best_score),
+                _ => "N/A".to_string(),
+            },
+        }
+    }
+
+    fn line_string(skill_eval: &SkillEval) -> String {
+        skill_eval
+            .line
+            .iter()
+            .map(|planned_skill| planned_skill.name.clone())
+            .collect::<Vec<String>>()
+            .join(" ➡ ")
     }
 }
@@ -0,0 +1,261 @@
+use crate::ai::ai_communication::{AICommunicator, AI};
+use crate::game::fight_record::{FightRecord, FightSummary, MAX_TURNS};
+use crate::game::game_manager::{GameManager, GameStatus};
+use crate::game::rotation::Rotation;
+use crate::gui::options::{AIOptions, GameOptions};
+use crate::gui::widgets::number_input::unsigned_number_input;
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+use egui::{Color32, Ui};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Contender {
+    name: String,
+    is_ai: bool,
+    summary: FightSummary,
+}
+
+// The AI's fights of a comparison that is still running
+struct AIFights {
+    ai: AICommunicator,
+    depth: u8,
+    runs: usize,
+
+    // The game every fight starts from
+    game: GameManager,
+
+    record: FightRecord,
+    records: Vec<FightRecord>,
+}
+
+pub struct CompareView {
+    // One rotation per line
+    rotations: String,
+    runs: String,
+    ai_depth: String,
+    include_ai: bool,
+
+    errors: Vec<String>,
+    contenders: Vec<Contender>,
+    ai_fights: Option<AIFights>,
+
+    game_options: Rc<RefCell<GameOptions>>,
+    ai_options: Rc<RefCell<AIOptions>>,
+}
+
+impl CompareView {
+    pub fn new(game_options: Rc<RefCell<GameOptions>>, ai_options: Rc<RefCell<AIOptions>>) -> Self {
+        Self {
+            rotations: String::new(),
+            runs: "20".to_string(),
+            ai_depth: '3'.to_string(),
+            include_ai: true,
+            errors: Vec::new(),
+            contenders: Vec::new(),
+            ai_fights: None,
+            game_options,
+            ai_options,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui) {
+        ui.label("Rotations (one per line)");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.rotations)
+                .hint_text("Target > Locker > Fury > Attack x2 > Curse")
+                .desired_width(f32::INFINITY)
+                .desired_rows(3),
+        );
+
+        egui::Grid::new("compare_view_options_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Fights");
+                unsigned_number_input(ui, &mut self.runs, 1000, 1);
+                ui.end_row();
+
+                ui.checkbox(&mut self.include_ai, "AI, depth");
+                unsigned_number_input(ui, &mut self.ai_depth, 10, 2);
+                ui.end_row();
+            });
+
+        if let Some(ai_fights) = &mut self.ai_fights {
+            // Results arrive from other threads, so keep polling while the AI is running
+            ui.ctx().request_repaint();
+
+            ui.add(
+                egui::ProgressBar::new(ai_fights.records.len() as f32 / ai_fights.runs as f32)
+                    .text(format!(
+                        "AI fight {} / {}, turn {}",
+                        ai_fights.records.len() + 1,
+                        ai_fights.runs,
+                        ai_fights.record.turns + 1
+                    )),
+            );
+
+            if ai_fights.ai.try_get_skill().is_ok() {
+                Self::record_ai_turn(ai_fights);
+
+                if ai_fights.records.len() == ai_fights.runs {
+                    let ai_fights = self.ai_fights.take().unwrap();
+                    self.contenders.push(Contender {
+                        name: format!("AI (depth {})", ai_fights.depth),
+                        is_ai: true,
+                        summary: FightSummary::from(ai_fights.records.as_slice()),
+                    });
+                }
+            }
+        } else if ui.button("Compare").clicked() {
+            self.start();
+        }
+
+        for error in &self.errors {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        if !self.contenders.is_empty() {
+            ui.separator();
+            self.draw_report(ui);
+        }
+    }
+
+    fn start(&mut self) {
+        self.errors.clear();
+        self.contenders.clear();
+
+        let game = self.game_options.borrow().create_game();
+        let runs: usize = self.runs.parse().unwrap();
+
+        let mut rotations = Vec::new();
+        for (index, line) in self.rotations.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match Rotation::parse(line, &game.player.get_base_type().as_player().skills) {
+                Ok(rotation) => rotations.push((line.trim().to_string(), rotation)),
+                Err(error) => self.errors.push(format!("Line {}: {}", index + 1, error)),
+            }
+        }
+        if !self.errors.is_empty() {
+            return;
+        }
+
+        for (name, rotation) in rotations {
+            let records: Vec<FightRecord> = (0..runs)
+                .map(|_| rotation.play_fight(game.clone(), MAX_TURNS))
+                .collect();
+
+            self.contenders.push(Contender {
+                name,
+                is_ai: false,
+                summary: FightSummary::from(records.as_slice()),
+            });
+        }
+
+        if self.include_ai {
+            let mut settings = self.ai_options.borrow().create_settings();
+            settings.depth = self.ai_depth.parse().unwrap();
+
+            // Most positions of these fights won't repeat, so they'd only push others out of the cache
+            let mut ai = AICommunicator::new(AI::new(game.clone(), settings.clone()), None);
+            ai.do_turn();
+
+            self.ai_fights = Some(AIFights {
+                ai,
+                depth: settings.depth,
+                runs,
+                record: FightRecord::new(&game),
+                game,
+                records: Vec::new(),
+            });
+        }
+    }
+
+    // Records the turn the AI just played, and starts the next fight if this one is over
+    fn record_ai_turn(ai_fights: &mut AIFights) {
+        let game = &ai_fights.ai.ai.game;
+        ai_fights.record.record_turn(game);
+
+        if matches!(game.get_status(), GameStatus::InProgress) && ai_fights.record.turns < MAX_TURNS
+        {
+            ai_fights.ai.do_turn();
+            return;
+        }
+
+        ai_fights.records.push(ai_fights.record.clone());
+        if ai_fights.records.len() < ai_fights.runs {
+            ai_fights.ai.ai.game = ai_fights.game.clone();
+            ai_fights.record = FightRecord::new(&ai_fights.game);
+            ai_fights.ai.do_turn();
+        }
+    }
+
+    fn draw_report(&self, ui: &mut Ui) {
+        let ai_summary = self
+            .contenders
+            .iter()
+            .find(|contender| contender.is_ai)
+            .map(|contender| &contender.summary);
+
+        egui::Grid::new("compare_view_report_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Rotation");
+                ui.label("Fights");
+                ui.label("Win Rate");
+                ui.label("Turns to Kill");
+                ui.label("Damage per Turn");
+                if ai_summary.is_some() {
+                    ui.label("Damage vs AI");
+                }
+                ui.end_row();
+
+                for contender in &self.contenders {
+                    let summary = &contender.summary;
+
+                    ui.label(&contender.name);
+                    ui.label(summary.fights.to_string());
+                    ui.label(format!("{:.0}%", 100.0 * summary.win_rate));
+                    ui.label(
+                        summary
+                            .turns_to_kill
+                            .map_or("-".to_string(), |turns| format!("{:.1}", turns)),
+                    );
+                    ui.label(format!("{:.0}", summary.damage_per_turn));
+                    if let Some(ai_summary) = ai_summary {
+                        // The AI deals no damage if it loses before hitting
+                        if ai_summary.damage_per_turn > 0.0 {
+                            ui.label(format!(
+                                "{:+.1}%",
+                                100.0
+                                    * (summary.damage_per_turn / ai_summary.damage_per_turn - 1.0)
+                            ));
+                        } else {
+                            ui.label("-");
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+
+        ui.heading("Mana Curve");
+        Plot::new("compare_view_mana_plot")
+            .legend(Legend::default())
+            .height(250.0)
+            .show(ui, |plot_ui| {
+                for contender in &self.contenders {
+                    let points: PlotPoints = contender
+                        .summary
+                        .mana_curve
+                        .iter()
+                        .enumerate()
+                        .map(|(turn, mp)| [turn as f64, *mp])
+                        .collect();
+                    plot_ui.line(Line::new(points).name(&contender.name));
+                }
+            });
+    }
+}
@@ -1,16 +1,27 @@
 use crate::game::types::gear::Slot;
-use crate::gui::gear::{GuiItem, ItemFilters};
-use crate::gui::options::GearOptions;
+use crate::gui::gear::{GuiItem, ItemFilters, SortKey};
+use crate::gui::item_comparison::ItemComparison;
+use crate::gui::item_query::{ItemQuery, QueryError};
+use crate::gui::options::{GameOptions, GearOptions};
+use crate::gui::saved_state::SavedGearSet;
 use crate::gui::utils;
-use crate::gui::widgets::optional_switch::switch;
-use egui::{Align, Layout, TextStyle, Ui, Vec2};
+use egui::{Align, Color32, Layout, TextStyle, Ui, Vec2};
 use egui_extras::Column;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+// What an `ItemComparison` is calculated from
+#[derive(PartialEq)]
+struct ComparisonKey {
+    item: GuiItem,
+    gear_set: Option<SavedGearSet>,
+    game_options: GameOptions,
+}
+
 pub struct GearView {
     max_items: usize,
+    game_options: Rc<RefCell<GameOptions>>,
     gear_options: Rc<RefCell<GearOptions>>,
     all_items: HashMap<Slot, Vec<GuiItem>>,
     item_list: Vec<GuiItem>,
@@ -18,25 +29,54 @@ pub struct GearView {
 
     current_item: Option<GuiItem>,
 
+    // Creating the players of a comparison is too slow to do on every frame
+    comparison: Option<(ComparisonKey, ItemComparison)>,
+
     pub search_string: String,
-    pub sort_string: String,
     pub filters: ItemFilters,
+
+    // An expression such as `level>=80 and not rare`, see `ItemQuery`
+    pub query_string: String,
+    query: Option<ItemQuery>,
+    query_error: Option<QueryError>,
+
+    pub sort_key: SortKey,
+    pub sort_descending: bool,
+
+    // Every key found in the loaded items
+    sort_keys: Vec<SortKey>,
+
+    // Narrows down `sort_keys`, since there are many of them
+    sort_string: String,
 }
 
 impl GearView {
-    pub fn new(gear: HashMap<Slot, Vec<GuiItem>>, gear_options: Rc<RefCell<GearOptions>>) -> Self {
+    pub fn new(
+        gear: HashMap<Slot, Vec<GuiItem>>,
+        game_options: Rc<RefCell<GameOptions>>,
+        gear_options: Rc<RefCell<GearOptions>>,
+    ) -> Self {
         let slot = gear_options.borrow().slot;
+        let sort_keys = SortKey::vec(gear.values().flatten());
 
         let mut result = Self {
             max_items: 100,
             current_slot: slot,
+            game_options,
             gear_options,
             all_items: gear,
             item_list: Vec::new(),
             current_item: None,
+            comparison: None,
             search_string: String::new(),
-            sort_string: String::new(),
             filters: ItemFilters::default(),
+            query_string: String::new(),
+            query: None,
+            query_error: None,
+            sort_key: SortKey::default(),
+            sort_descending: false,
+            sort_keys,
+            sort_string: String::new(),
         };
         result.update_item_list();
 
@@ -44,9 +84,11 @@ impl GearView {
     }
 
     pub fn update_item_list(&mut self) {
+        self.current_slot = self.gear_options.borrow().slot;
+
         let items_for_slot = self
             .all_items
-            .get(&self.gear_options.borrow().slot)
+            .get(&self.current_slot)
             .unwrap_or(&Vec::new())
             .clone();
 
@@ -58,14 +100,41 @@ impl GearView {
                 .to_lowercase()
                 .contains(&self.search_string.to_lowercase())
                 && self.filters.allow(&item.tags)
+                && self.query.iter().all(|query| query.matches(&item))
             {
                 item_list.push(item);
             }
         }
 
+        item_list.sort_by(|a, b| {
+            let ordering = self.sort_key.compare(a, b);
+            let ordering = if self.sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+
+            ordering.then_with(|| a.name.cmp(&b.name))
+        });
+
         self.item_list = item_list;
     }
 
+    // Items aren't filtered by the query while it has an error
+    fn update_query(&mut self) {
+        self.query = None;
+        self.query_error = None;
+
+        if !self.query_string.trim().is_empty() {
+            match ItemQuery::parse(&self.query_string) {
+                Ok(query) => self.query = Some(query),
+                Err(error) => self.query_error = Some(error),
+            }
+        }
+
+        self.update_item_list();
+    }
+
     pub fn draw(&mut self, ui: &mut Ui) {
         if self.current_slot != self.gear_options.borrow().slot {
             // The slot was changed from the side panel, we need to update the item list
@@ -111,50 +180,50 @@ impl GearView {
 
         ui.separator();
 
+        ui.heading("Filter: ");
+        let query_edit = egui::TextEdit::multiline(&mut self.query_string)
+            .hint_text("level>=80 and bonuses.crit>=5 and not rare")
+            .desired_rows(2);
+        if ui.add(query_edit).changed() {
+            self.update_query();
+        }
+        if let Some(error) = &self.query_error {
+            ui.colored_label(Color32::RED, error.to_string());
+        }
+
+        ui.separator();
+
         ui.heading("Sort by: ");
-        if ui.text_edit_singleline(&mut self.sort_string).changed() {
+        ui.add(egui::TextEdit::singleline(&mut self.sort_string).hint_text("Search keys"));
+        let mut sort_changed = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::new("gear_view_sort_key_combo_box", "")
+                .selected_text(self.sort_key.to_string())
+                .show_ui(ui, |ui| {
+                    let search = self.sort_string.to_lowercase();
+                    for key in &self.sort_keys {
+                        let name = key.to_string();
+                        if name.to_lowercase().contains(&search) {
+                            sort_changed |= ui
+                                .selectable_value(&mut self.sort_key, key.clone(), name)
+                                .changed();
+                        }
+                    }
+                });
+            sort_changed |= ui
+                .checkbox(&mut self.sort_descending, "Descending")
+                .changed();
+        });
+        if sort_changed {
             self.update_item_list();
         }
 
         ui.separator();
 
         ui.heading("Filter by tags:");
-        egui::Grid::new("gear_view_filters_grid").show(ui, |ui| {
-            ui.label("Dragon Amulet");
-            if switch(ui, &mut self.filters.dragon_amulet).changed() {
-                self.update_item_list();
-            }
-            ui.end_row();
-
-            ui.label("Dragon Coins");
-            if switch(ui, &mut self.filters.dragon_coins).changed() {
-                self.update_item_list();
-            }
-            ui.end_row();
-
-            ui.label("Rare");
-            if switch(ui, &mut self.filters.rare).changed() {
-                self.update_item_list();
-            }
-            ui.end_row();
-
-            ui.label("Temporary");
-            if switch(ui, &mut self.filters.temporary).changed() {
-                self.update_item_list();
-            }
-            ui.end_row();
-
-            ui.label("Special Offer");
-            if switch(ui, &mut self.filters.special_offer).changed() {
-                self.update_item_list();
-            }
-            ui.end_row();
-
-            ui.label("War");
-            if switch(ui, &mut self.filters.war).changed() {
-                self.update_item_list();
-            }
-        });
+        if self.filters.draw(ui, "gear_view_filters_grid") {
+            self.update_item_list();
+        }
     }
 
     fn draw_middle_panel(&mut self, ui: &mut Ui) {
@@ -231,6 +300,17 @@ impl GearView {
     fn draw_right_panel(&mut self, ui: &mut Ui) {
         if let Some(item) = &self.current_item {
             ui.heading(&item.name);
+
+            let mut gear_options = self.gear_options.borrow_mut();
+            let mut owned = gear_options.owned_items.contains(&item.pedia_page_id);
+            if ui.checkbox(&mut owned, "Owned").changed() {
+                if owned {
+                    gear_options.owned_items.insert(item.pedia_page_id);
+                } else {
+                    gear_options.owned_items.remove(&item.pedia_page_id);
+                }
+            }
+            drop(gear_options);
             ui.separator();
 
             let size_of_column = ui.available_width() / 3.0;
@@ -263,6 +343,21 @@ impl GearView {
                 ui.label(tags_string);
                 ui.separator();
             }
+
+            let gear_options = self.gear_options.borrow();
+            let gear_set = gear_options.get_current_set();
+            let key = ComparisonKey {
+                item: item.clone(),
+                gear_set: gear_set.map(SavedGearSet::from),
+                game_options: self.game_options.borrow().clone(),
+            };
+
+            let comparison = match self.comparison.take() {
+                Some((old_key, comparison)) if old_key == key => comparison,
+                _ => ItemComparison::new(&key.game_options, gear_set, item),
+            };
+            comparison.draw(ui);
+            self.comparison = Some((key, comparison));
         }
     }
 }
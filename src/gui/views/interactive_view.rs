@@ -1,6 +1,11 @@
-use crate::game::game_manager::GameManager;
-use crate::gui::options::GameOptions;
+use crate::ai::ai_communication::{AICommunicator, AITurn, AI};
+use crate::ai::rotation_cache::SharedRotationCache;
+use crate::ai::types::objective::Objective;
+use crate::game::game_manager::{GameManager, GameStatus};
+use crate::game::types::hit::{ForcedHit, HitOutcome};
+use crate::gui::options::{AIOptions, GameOptions};
 use crate::gui::utils;
+use crate::gui::widgets::entity_editor::{EditorAction, EntityEditor};
 use egui::{CollapsingHeader, Color32, Ui};
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -27,25 +32,83 @@ impl GameHistoryManager {
     }
 
     pub fn do_turn(&mut self) {
-        self.game_history.push(self.game.clone());
+        self.checkpoint();
         self.game.do_turn();
     }
 
+    // Saves the current game, so changes made to it can be rolled back
+    pub fn checkpoint(&mut self) {
+        self.game_history.push(self.game.clone());
+    }
+
     pub fn rollback(&mut self) {
         self.game = self.game_history.pop().unwrap();
     }
 }
 
+// The AI's suggestion for the current turn
+struct Suggestion {
+    turn: Option<AITurn>,
+    show_win_probability: bool,
+}
+
+// A hit outcome entered by the user, `None` rolls randomly
+struct OutcomeInput {
+    outcome: Option<HitOutcome>,
+
+    // Empty to use the calculated damage
+    damage: String,
+}
+
+// A turn waiting for the user to enter its outcomes
+struct PendingTurn {
+    skill: usize,
+
+    // Indexed like `GameManager::get_entity`.
+    // `hits` are the entity's hits, and `stuns` are stuns on the entity.
+    hits: Vec<Vec<OutcomeInput>>,
+    stuns: Vec<Vec<Option<bool>>>,
+}
+
 pub struct InteractiveView {
     game: Option<GameHistoryManager>,
+
+    // Whether the user enters the outcome of every hit and stun instead of rolling them
+    control_outcomes: bool,
+    pending_turn: Option<PendingTurn>,
+
+    // Edits the entity that was clicked in the game grid
+    editor: Option<EntityEditor>,
+
+    // Whether the AI suggests a skill every turn
+    suggest: bool,
+    suggestion: Option<Suggestion>,
+
+    // Kept between turns, so its thread pool or web workers are only created once
+    ai: Option<AICommunicator>,
+
     game_options: Rc<RefCell<GameOptions>>,
+    ai_options: Rc<RefCell<AIOptions>>,
+    rotation_cache: SharedRotationCache,
 }
 
 impl InteractiveView {
-    pub fn new(game_options: Rc<RefCell<GameOptions>>) -> Self {
+    pub fn new(
+        game_options: Rc<RefCell<GameOptions>>,
+        ai_options: Rc<RefCell<AIOptions>>,
+        rotation_cache: SharedRotationCache,
+    ) -> Self {
         Self {
             game: None,
+            control_outcomes: false,
+            pending_turn: None,
+            editor: None,
+            suggest: false,
+            suggestion: None,
+            ai: None,
             game_options,
+            ai_options,
+            rotation_cache,
         }
     }
 
@@ -53,15 +116,26 @@ impl InteractiveView {
         if let Some(game_history_manager) = &self.game {
             let game = game_history_manager.current();
 
-            ui.heading(format!("Turn {}", game.turn));
+            ui.horizontal(|ui| {
+                ui.heading(format!("Turn {}", game.turn));
+                ui.checkbox(&mut self.suggest, "Suggest");
+                ui.checkbox(&mut self.control_outcomes, "Control Outcomes");
+            });
             let skills = game
                 .player
                 .get_base_type()
                 .as_player()
                 .get_available_skills();
-            self.draw_skillset(ui, skills);
+            self.update_suggestion(ui);
+            if self.pending_turn.is_some() {
+                self.draw_pending_turn(ui);
+            } else {
+                self.draw_skillset(ui, skills);
+            }
+            self.draw_suggestion(ui);
             ui.separator();
             self.draw_game(ui);
+            self.draw_editor(ui);
         } else {
             self.game = Some(GameHistoryManager::new(
                 self.game_options.borrow().create_game(),
@@ -69,41 +143,333 @@ impl InteractiveView {
         }
     }
 
+    // Starts a search on the current turn if needed, and collects its result
+    fn update_suggestion(&mut self, ui: &mut Ui) {
+        let game = self.game.as_ref().unwrap().current();
+        if !self.suggest || !matches!(game.get_status(), GameStatus::InProgress) {
+            self.cancel_suggestion();
+            return;
+        }
+
+        if let (Some(suggestion), Some(ai)) = (&mut self.suggestion, &mut self.ai) {
+            if suggestion.turn.is_none() {
+                // Results arrive from other threads, so keep polling while the AI is running
+                ui.ctx().request_repaint();
+
+                if let Ok(turn) = ai.try_get_skill() {
+                    suggestion.turn = Some(turn);
+                }
+            }
+            return;
+        }
+
+        let settings = self.ai_options.borrow().create_settings();
+        let show_win_probability = settings.objective == Objective::WinProbability;
+        let ai = match &mut self.ai {
+            Some(ai) => {
+                ai.set_position(game.clone(), settings);
+                ai
+            }
+            None => self.ai.insert(AICommunicator::new(
+                AI::new(game.clone(), settings),
+                Some(self.rotation_cache.clone()),
+            )),
+        };
+        ai.do_turn();
+
+        self.suggestion = Some(Suggestion {
+            turn: None,
+            show_win_probability,
+        });
+    }
+
+    // Stops the search of the current suggestion, if it's still running
+    fn cancel_suggestion(&mut self) {
+        if self.suggestion.take().is_none() {
+            return;
+        }
+        if let Some(ai) = &mut self.ai {
+            ai.cancel();
+        }
+    }
+
     fn draw_skillset(&mut self, ui: &mut Ui, skills: Vec<usize>) {
+        let suggested_skill = self
+            .suggestion
+            .as_ref()
+            .and_then(|suggestion| suggestion.turn.as_ref())
+            .and_then(|turn| turn.plan.index);
+
         ui.horizontal(|ui| {
             for skill in skills {
                 let game = self.game.as_mut().unwrap();
                 let mut _player_ref = game.current_mut().player.get_base_type_mut();
                 let player = _player_ref.as_player();
 
-                if ui.button(player.skills[skill].name.clone()).clicked() {
-                    player.set_current_skill(skill);
-                    self.game.as_mut().unwrap().do_turn();
+                let mut button = egui::Button::new(player.skills[skill].name.clone());
+                if suggested_skill == Some(skill) {
+                    button = button.fill(Color32::DARK_GREEN);
+                }
+
+                if ui.add(button).clicked() {
+                    if self.control_outcomes {
+                        self.pending_turn = Some(Self::prepare_turn(game.current(), skill));
+                    } else {
+                        player.set_current_skill(skill);
+                        self.game.as_mut().unwrap().do_turn();
+                        self.on_game_changed();
+                    }
                 }
             }
 
             let game = self.game.as_mut().unwrap();
             if !game.game_history.is_empty() && ui.button("Back").clicked() {
                 game.rollback();
+                self.on_game_changed();
             }
         });
     }
 
-    fn draw_game(&self, ui: &mut Ui) {
+    // Plays the turn on a copy of the game, to find out how many hits and stuns it rolls
+    fn prepare_turn(game: &GameManager, skill: usize) -> PendingTurn {
+        let mut game = game.clone();
+        for index in 0..game.entity_count() {
+            let entity = game.get_entity_mut(index);
+            entity.hit_rolls = 0;
+            entity.stun_rolls = 0;
+        }
+
+        game.player
+            .get_base_type_mut()
+            .as_player()
+            .set_current_skill(skill);
+        game.do_turn();
+
+        let mut hits = Vec::new();
+        let mut stuns = Vec::new();
+        for index in 0..game.entity_count() {
+            let entity = game.get_entity(index);
+            hits.push(
+                (0..entity.hit_rolls)
+                    .map(|_| OutcomeInput {
+                        outcome: None,
+                        damage: String::new(),
+                    })
+                    .collect(),
+            );
+            stuns.push(vec![None; entity.stun_rolls]);
+        }
+
+        PendingTurn { skill, hits, stuns }
+    }
+
+    fn draw_pending_turn(&mut self, ui: &mut Ui) {
+        let game = self.game.as_mut().unwrap();
+        let pending_turn = self.pending_turn.as_mut().unwrap();
+
+        let skill_name = game.current().player.get_base_type().as_player().skills
+            [pending_turn.skill]
+            .name
+            .clone();
+        ui.label(format!("Outcomes of {}", skill_name));
+        // Rolls depend on earlier outcomes, so a turn may roll more than listed here
+        ui.label("Rolls that aren't listed, or are set to Random, are rolled randomly.");
+
+        egui::Grid::new("interactive_view_outcomes_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for index in 0..game.current().entity_count() {
+                    let name = game.current().get_entity(index).name.clone();
+
+                    for (hit_index, input) in pending_turn.hits[index].iter_mut().enumerate() {
+                        ui.label(format!("{} hit {}", name, hit_index + 1));
+                        egui::ComboBox::new(
+                            format!("interactive_view_hit_{}_{}", index, hit_index),
+                            "",
+                        )
+                        .selected_text(
+                            input
+                                .outcome
+                                .map_or("Random".to_string(), |outcome| outcome.to_string()),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut input.outcome, None, "Random");
+                            for outcome in HitOutcome::vec() {
+                                ui.selectable_value(
+                                    &mut input.outcome,
+                                    Some(outcome),
+                                    outcome.to_string(),
+                                );
+                            }
+                        });
+                        if input.outcome.is_some() && input.outcome != Some(HitOutcome::Miss) {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut input.damage)
+                                    .hint_text("Damage")
+                                    .desired_width(80.0),
+                            );
+                        }
+                        ui.end_row();
+                    }
+
+                    for (stun_index, landed) in pending_turn.stuns[index].iter_mut().enumerate() {
+                        ui.label(format!("Stun {} on {}", stun_index + 1, name));
+                        egui::ComboBox::new(
+                            format!("interactive_view_stun_{}_{}", index, stun_index),
+                            "",
+                        )
+                        .selected_text(match landed {
+                            None => "Random",
+                            Some(true) => "Landed",
+                            Some(false) => "Resisted",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(landed, None, "Random");
+                            ui.selectable_value(landed, Some(true), "Landed");
+                            ui.selectable_value(landed, Some(false), "Resisted");
+                        });
+                        ui.end_row();
+                    }
+                }
+            });
+
+        let mut confirmed = false;
+        ui.horizontal(|ui| {
+            if ui.button("Confirm").clicked() {
+                let pending_turn = self.pending_turn.take().unwrap();
+                Self::play_pending_turn(game, pending_turn);
+                confirmed = true;
+            }
+            if ui.button("Cancel").clicked() {
+                self.pending_turn = None;
+            }
+        });
+        if confirmed {
+            self.on_game_changed();
+        }
+    }
+
+    fn play_pending_turn(game: &mut GameHistoryManager, pending_turn: PendingTurn) {
+        let current = game.current_mut();
+        for index in 0..current.entity_count() {
+            let entity = current.get_entity_mut(index);
+            entity.forced_hits = pending_turn.hits[index]
+                .iter()
+                .map(|input| {
+                    input.outcome.map(|outcome| ForcedHit {
+                        outcome,
+                        damage: input.damage.trim().parse().ok(),
+                    })
+                })
+                .collect();
+            entity.forced_stuns = pending_turn.stuns[index].iter().copied().collect();
+        }
+
+        current
+            .player
+            .get_base_type_mut()
+            .as_player()
+            .set_current_skill(pending_turn.skill);
+        game.do_turn();
+
+        // Outcomes the turn didn't use shouldn't leak into the next turns
+        let current = game.current_mut();
+        for index in 0..current.entity_count() {
+            let entity = current.get_entity_mut(index);
+            entity.forced_hits.clear();
+            entity.forced_stuns.clear();
+        }
+    }
+
+    fn draw_suggestion(&self, ui: &mut Ui) {
+        let Some(suggestion) = &self.suggestion else {
+            return;
+        };
+
+        let Some(turn) = &suggestion.turn else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Thinking...");
+            });
+            return;
+        };
+
+        let show_win_probability = suggestion.show_win_probability;
+        ui.label(format!(
+            "Suggested: {} ({})",
+            turn.skill_name,
+            utils::eval_string(&turn.plan.eval, show_win_probability)
+        ));
+        for constraint in &turn.unmet_constraints {
+            ui.colored_label(Color32::YELLOW, format!("Unmet constraint: {}", constraint));
+        }
+        if !turn.alternatives.is_empty() {
+            ui.label(format!(
+                "Alternatives: {}",
+                turn.alternatives
+                    .iter()
+                    .map(|alternative| format!(
+                        "{} ({})",
+                        alternative.line[0].name,
+                        utils::eval_string(&alternative.eval, show_win_probability)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    // The AI's suggestion and the editor's values are outdated once the game changes
+    fn on_game_changed(&mut self) {
+        self.cancel_suggestion();
+
+        let game = self.game.as_ref().unwrap().current();
+        if let Some(editor) = &mut self.editor {
+            *editor = EntityEditor::new(game, editor.index);
+        }
+    }
+
+    fn draw_editor(&mut self, ui: &mut Ui) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+
+        ui.separator();
+        match editor.draw(ui) {
+            Some(EditorAction::Apply) => {
+                let game = self.game.as_mut().unwrap();
+                game.checkpoint();
+                editor.apply(game.current_mut());
+
+                // The outcomes were counted for the game before the changes
+                self.pending_turn = None;
+                self.on_game_changed();
+            }
+            Some(EditorAction::Close) => self.editor = None,
+            None => {}
+        }
+    }
+
+    fn draw_game(&mut self, ui: &mut Ui) {
         let game = &self.game.as_ref().unwrap().game;
         let mut entities = vec![game.player.get_base_entity()];
         for enemy in &game.enemies {
             entities.push(enemy.get_base_entity());
         }
 
+        let mut clicked_entity = None;
         let col_width = ui.available_width() / (entities.len() + 1) as f32;
         egui::Grid::new("game_grid")
             .min_col_width(col_width)
             .max_col_width(col_width)
-            .show(ui, move |ui| {
-                // Names
-                for entity in &entities {
-                    ui.heading(&entity.name);
+            .show(ui, |ui| {
+                // Names, which open the editor when clicked
+                for (index, entity) in entities.iter().enumerate() {
+                    let name = egui::Label::new(egui::RichText::new(&entity.name).heading())
+                        .sense(egui::Sense::click());
+                    if ui.add(name).on_hover_text("Click to edit").clicked() {
+                        clicked_entity = Some(index);
+                    }
                 }
                 ui.end_row();
 
@@ -156,7 +522,7 @@ impl InteractiveView {
                             ui.push_id(format!("entity_details_resists_label_{}", index), |ui| {
                                 ui.collapsing("Resists", |ui| {
                                     let mut resists = entity.resists.clone();
-                                    resists.merge(&entity.gear_bonuses);
+                                    resists.merge(&entity.gear_resists);
                                     utils::draw_dict(
                                         ui,
                                         &resists,
@@ -231,5 +597,9 @@ impl InteractiveView {
                     });
                 }
             });
+
+        if let Some(index) = clicked_entity {
+            self.editor = Some(EntityEditor::new(game, index));
+        }
     }
 }
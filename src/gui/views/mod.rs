@@ -1,4 +1,7 @@
 pub mod ai_view;
+pub mod compare_view;
 pub mod gear_view;
 pub mod interactive_view;
+pub mod optimizer_view;
+pub mod rotation_view;
 pub mod side_panel_view;
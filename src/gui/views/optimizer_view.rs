@@ -0,0 +1,427 @@
+use crate::game::rotation::Rotation;
+use crate::game::types::gear::Slot;
+use crate::gui::gear::{GuiItem, ItemFilters};
+use crate::gui::gear_optimizer::{
+    GearOptimizer, OptimizedBuild, OptimizerObjective, OptimizerSettings,
+};
+use crate::gui::optimizer_task::{Optimizer, OptimizerTask};
+use crate::gui::options::{GameOptions, GearOptions};
+use crate::gui::sensitivity::SensitivityReport;
+use crate::gui::stat_optimizer::{
+    OptimizedStats, StatObjective, StatOptimizer, StatOptimizerSettings,
+};
+use crate::gui::stats_handler::STATS;
+use crate::gui::widgets::number_input::unsigned_number_input;
+use egui::{Color32, Ui};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc::TryRecvError;
+
+#[derive(PartialEq)]
+enum OptimizerMode {
+    Gear,
+    Stats,
+    Sensitivity,
+}
+
+pub struct OptimizerView {
+    mode: OptimizerMode,
+    items: HashMap<Slot, Vec<GuiItem>>,
+
+    objective: OptimizerObjective,
+    objectives: Vec<OptimizerObjective>,
+    filters: ItemFilters,
+    owned_only: bool,
+    max_level: String,
+    results: String,
+    beam_width: String,
+
+    stat_objective: StatObjective,
+    stat_step: String,
+
+    // Only used when optimizing the win rate
+    rotation: String,
+    fights: String,
+
+    error: Option<String>,
+    gear_task: Option<OptimizerTask<GearOptimizer>>,
+    stat_task: Option<OptimizerTask<StatOptimizer>>,
+    builds: Vec<OptimizedBuild>,
+    stats: Option<OptimizedStats>,
+    sensitivity: Option<SensitivityReport>,
+
+    game_options: Rc<RefCell<GameOptions>>,
+    gear_options: Rc<RefCell<GearOptions>>,
+}
+
+impl OptimizerView {
+    pub fn new(
+        items: HashMap<Slot, Vec<GuiItem>>,
+        game_options: Rc<RefCell<GameOptions>>,
+        gear_options: Rc<RefCell<GearOptions>>,
+    ) -> Self {
+        let objectives = OptimizerObjective::vec(items.values().flatten());
+
+        Self {
+            mode: OptimizerMode::Gear,
+            items,
+            objective: OptimizerObjective::AttackDamage,
+            objectives,
+            filters: ItemFilters::default(),
+            owned_only: false,
+            max_level: "90".to_string(),
+            results: "5".to_string(),
+            beam_width: "50".to_string(),
+            stat_objective: StatObjective::AttackDamage,
+            stat_step: "5".to_string(),
+            rotation: String::new(),
+            fights: "20".to_string(),
+            error: None,
+            gear_task: None,
+            stat_task: None,
+            builds: Vec::new(),
+            stats: None,
+            sensitivity: None,
+            game_options,
+            gear_options,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, OptimizerMode::Gear, "Gear");
+            ui.selectable_value(&mut self.mode, OptimizerMode::Stats, "Stats");
+            ui.selectable_value(&mut self.mode, OptimizerMode::Sensitivity, "Sensitivity");
+        });
+        ui.separator();
+
+        match self.mode {
+            OptimizerMode::Gear => self.draw_gear(ui),
+            OptimizerMode::Stats => self.draw_stats(ui),
+            OptimizerMode::Sensitivity => self.draw_sensitivity(ui),
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    fn draw_gear(&mut self, ui: &mut Ui) {
+        egui::Grid::new("optimizer_view_options_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Objective");
+                egui::ComboBox::new("optimizer_view_objective_combo_box", "")
+                    .selected_text(self.objective.to_string())
+                    .show_ui(ui, |ui| {
+                        for objective in &self.objectives {
+                            ui.selectable_value(
+                                &mut self.objective,
+                                objective.clone(),
+                                objective.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                if self.objective == OptimizerObjective::WinRate {
+                    self.draw_rotation_options(ui);
+
+                    ui.label("");
+                    ui.label("Builds are picked by basic attack damage, then ranked by win rate");
+                    ui.end_row();
+                }
+
+                ui.label("Max Item Level");
+                unsigned_number_input(ui, &mut self.max_level, 90, 1);
+                ui.end_row();
+
+                ui.label("Owned Items Only");
+                ui.checkbox(&mut self.owned_only, "");
+                ui.end_row();
+
+                ui.label("Builds");
+                unsigned_number_input(ui, &mut self.results, 50, 1);
+                ui.end_row();
+
+                // Wider searches are slower, but less likely to miss good builds
+                ui.label("Search Width");
+                unsigned_number_input(ui, &mut self.beam_width, 500, 1);
+                ui.end_row();
+            });
+
+        ui.heading("Filter by tags:");
+        self.filters.draw(ui, "optimizer_view_filters_grid");
+
+        if self.gear_task.is_some() {
+            if let Some(builds) = Self::poll_task(ui, &mut self.gear_task, &mut self.error) {
+                self.builds = builds;
+            }
+        } else if ui.button("Optimize").clicked() {
+            self.optimize_gear();
+        }
+
+        if !self.builds.is_empty() {
+            ui.separator();
+            self.draw_builds(ui);
+        }
+    }
+
+    fn draw_rotation_options(&mut self, ui: &mut Ui) {
+        ui.label("Rotation");
+        ui.add(
+            egui::TextEdit::singleline(&mut self.rotation)
+                .hint_text("Target > Locker > Fury > Attack x2 > Curse"),
+        );
+        ui.end_row();
+
+        ui.label("Fights");
+        unsigned_number_input(ui, &mut self.fights, 1000, 1);
+        ui.end_row();
+    }
+
+    fn draw_stats(&mut self, ui: &mut Ui) {
+        egui::Grid::new("optimizer_view_stats_options_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Objective");
+                egui::ComboBox::new("optimizer_view_stat_objective_combo_box", "")
+                    .selected_text(self.stat_objective.to_string())
+                    .show_ui(ui, |ui| {
+                        for objective in StatObjective::vec() {
+                            ui.selectable_value(
+                                &mut self.stat_objective,
+                                objective,
+                                objective.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                if self.stat_objective == StatObjective::WinRate {
+                    self.draw_rotation_options(ui);
+                }
+
+                // Smaller steps find better allocations, but take longer
+                ui.label("Step");
+                unsigned_number_input(ui, &mut self.stat_step, 50, 1);
+                ui.end_row();
+            });
+        ui.label("Uses the current build's gear");
+
+        if self.stat_task.is_some() {
+            if let Some(stats) = Self::poll_task(ui, &mut self.stat_task, &mut self.error) {
+                self.stats = Some(stats);
+            }
+        } else if ui.button("Optimize").clicked() {
+            self.optimize_stats();
+        }
+
+        let Some(result) = &self.stats else {
+            return;
+        };
+        ui.separator();
+
+        egui::Grid::new("optimizer_view_stats_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for stat in STATS {
+                    ui.label(stat);
+                    ui.label(result.stats.get(stat).to_string());
+                    ui.end_row();
+                }
+            });
+
+        match result.objective {
+            StatObjective::AttackDamage => {
+                ui.label(format!("{:.1} basic attack damage", result.score.damage));
+            }
+            StatObjective::WinRate => {
+                ui.label(format!("{:.0}% win rate", 100.0 * result.score.win_rate));
+                ui.label(format!("{:.1} damage per turn", result.score.damage));
+            }
+        }
+        if result.unspent > 0 {
+            ui.label(format!(
+                "{} points didn't improve the score, and were left unspent",
+                result.unspent
+            ));
+        }
+
+        if ui.button("Use These Stats").clicked() {
+            self.game_options.borrow_mut().stats = result.stats.clone();
+        }
+    }
+
+    fn draw_sensitivity(&mut self, ui: &mut Ui) {
+        ui.label("How much one more point changes the basic attacks of the current build");
+        ui.label("Skills, DoTs and armor effects aren't included");
+        if ui.button("Analyze").clicked() {
+            let gear_options = self.gear_options.borrow();
+            self.sensitivity = Some(SensitivityReport::new(
+                &self.game_options.borrow(),
+                gear_options.get_current_set(),
+            ));
+        }
+
+        if let Some(report) = &self.sensitivity {
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| report.draw(ui));
+        }
+    }
+
+    // Shows the progress of a running optimizer, and returns its result once it's done.
+    // The task is dropped once it's done, failed or cancelled.
+    fn poll_task<O: Optimizer>(
+        ui: &mut Ui,
+        task: &mut Option<OptimizerTask<O>>,
+        error: &mut Option<String>,
+    ) -> Option<O::Output> {
+        let running_task = task.as_mut()?;
+
+        // The optimizer runs in the background, so keep polling until it's done
+        ui.ctx().request_repaint();
+
+        match running_task.try_get_result() {
+            Ok(output) => {
+                *task = None;
+                Some(output)
+            }
+            Err(TryRecvError::Empty) => {
+                let progress = running_task.progress().clone();
+                ui.horizontal(|ui| {
+                    ui.add(egui::ProgressBar::new(progress.fraction).text(progress.text));
+                    if ui.button("Cancel").clicked() {
+                        *task = None;
+                    }
+                });
+                None
+            }
+            Err(TryRecvError::Disconnected) => {
+                *error = Some("The optimizer stopped unexpectedly".to_string());
+                *task = None;
+                None
+            }
+        }
+    }
+
+    // Shows the error if the rotation can't be parsed
+    fn parse_rotation(&mut self, game_options: &GameOptions) -> Option<Rotation> {
+        let game = game_options.create_game();
+        match Rotation::parse(
+            &self.rotation,
+            &game.player.get_base_type().as_player().skills,
+        ) {
+            Ok(rotation) => Some(rotation),
+            Err(error) => {
+                self.error = Some(error.to_string());
+                None
+            }
+        }
+    }
+
+    fn optimize_stats(&mut self) {
+        self.error = None;
+        self.stats = None;
+
+        let game_options = self.game_options.borrow().clone();
+
+        let rotation = if self.stat_objective == StatObjective::WinRate {
+            let Some(rotation) = self.parse_rotation(&game_options) else {
+                return;
+            };
+            Some(rotation)
+        } else {
+            None
+        };
+
+        let settings = StatOptimizerSettings {
+            objective: self.stat_objective,
+            step: self.stat_step.parse().unwrap(),
+            rotation,
+            fights: self.fights.parse().unwrap(),
+        };
+
+        let gear_set = self.gear_options.borrow().get_current_set().cloned();
+        self.stat_task = Some(OptimizerTask::new(StatOptimizer::new(
+            game_options,
+            gear_set,
+            settings,
+        )));
+    }
+
+    fn optimize_gear(&mut self) {
+        self.error = None;
+        self.builds.clear();
+
+        let game_options = self.game_options.borrow().clone();
+
+        let rotation = if self.objective == OptimizerObjective::WinRate {
+            let Some(rotation) = self.parse_rotation(&game_options) else {
+                return;
+            };
+            Some(rotation)
+        } else {
+            None
+        };
+
+        let settings = OptimizerSettings {
+            objective: self.objective.clone(),
+            filters: self.filters.clone(),
+            owned_only: self.owned_only,
+            max_level: self.max_level.parse().unwrap(),
+            results: self.results.parse().unwrap(),
+            beam_width: self.beam_width.parse().unwrap(),
+            rotation,
+            fights: self.fights.parse().unwrap(),
+        };
+
+        match GearOptimizer::new(
+            game_options,
+            settings,
+            &self.items,
+            &self.gear_options.borrow().owned_items,
+        ) {
+            Ok(optimizer) => self.gear_task = Some(OptimizerTask::new(optimizer)),
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+
+    fn draw_builds(&mut self, ui: &mut Ui) {
+        let mut saved_build = None;
+
+        for (index, build) in self.builds.iter().enumerate() {
+            let score = match &self.objective {
+                OptimizerObjective::AttackDamage => {
+                    format!("{:.1} basic attack damage", build.score)
+                }
+                OptimizerObjective::Resist(elem) => format!(
+                    "{}% {}, {:.1} basic attack damage",
+                    build.score, elem, build.attack_damage
+                ),
+                OptimizerObjective::WinRate => format!(
+                    "{:.0}% win rate, {:.1} basic attack damage",
+                    100.0 * build.score,
+                    build.attack_damage
+                ),
+            };
+
+            ui.push_id(index, |ui| {
+                ui.collapsing(format!("#{}: {}", index + 1, score), |ui| {
+                    build.gear_set.draw(ui);
+                    if ui.button("Save as Build").clicked() {
+                        saved_build = Some(index);
+                    }
+                });
+            });
+        }
+
+        if let Some(index) = saved_build {
+            let mut gear_set = self.builds[index].gear_set.clone();
+            gear_set.name = format!("Optimized {}", index + 1);
+            self.gear_options.borrow_mut().add_gear_set(gear_set);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::ai::types::state_summary::StateSummary;
+use crate::game::game_manager::GameStatus;
+use crate::game::rotation::{ParseError, Rotation, SimulatedStep};
+use crate::gui::options::GameOptions;
+use egui::{Color32, Ui};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub struct RotationView {
+    text: String,
+    simulation: Option<Result<Vec<SimulatedStep>, ParseError>>,
+
+    game_options: Rc<RefCell<GameOptions>>,
+}
+
+impl RotationView {
+    pub fn new(game_options: Rc<RefCell<GameOptions>>) -> Self {
+        Self {
+            text: String::new(),
+            simulation: None,
+            game_options,
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            let text_edit = ui.add(
+                egui::TextEdit::singleline(&mut self.text)
+                    .hint_text("Target > Locker > Fury > Attack x2 > Curse")
+                    .desired_width(ui.available_width() * 0.8),
+            );
+            let submitted = text_edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if ui.button("Simulate").clicked() || submitted {
+                self.simulate();
+            }
+        });
+
+        match &self.simulation {
+            Some(Ok(steps)) => {
+                ui.separator();
+                Self::draw_simulation(ui, steps);
+            }
+            Some(Err(error)) => {
+                ui.colored_label(Color32::RED, error.to_string());
+            }
+            None => {}
+        }
+    }
+
+    fn simulate(&mut self) {
+        let game = self.game_options.borrow().create_game();
+        let rotation = Rotation::parse(&self.text, &game.player.get_base_type().as_player().skills);
+
+        self.simulation = Some(rotation.map(|rotation| rotation.simulate(game)));
+    }
+
+    fn draw_simulation(ui: &mut Ui, steps: &[SimulatedStep]) {
+        let Some(last_step) = steps.last() else {
+            return;
+        };
+
+        let illegal_steps = steps.iter().filter(|step| step.result.is_err()).count();
+        let turns = last_step.game.turn - 1;
+        ui.label(match last_step.game.get_status() {
+            GameStatus::Won => format!("Won on turn {}", turns),
+            GameStatus::Lost => format!("Lost on turn {}", turns),
+            GameStatus::InProgress => format!(
+                "Enemy HP at {:.1}% after {} turns",
+                StateSummary::from(&last_step.game).enemies_hp_percent(),
+                turns
+            ),
+        });
+        if illegal_steps > 0 {
+            ui.colored_label(
+                Color32::RED,
+                format!("{} illegal steps were skipped", illegal_steps),
+            );
+        }
+
+        egui::Grid::new("rotation_view_steps_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Turn");
+                ui.label("Skill");
+                ui.label("Result");
+                ui.label("Enemy HP");
+                ui.label("Player HP");
+                ui.label("Player MP");
+                ui.end_row();
+
+                for step in steps {
+                    let state = StateSummary::from(&step.game);
+                    let skill_name = step.game.player.get_base_type().as_player().skills
+                        [step.skill]
+                        .name
+                        .clone();
+
+                    ui.label(step.turn.to_string());
+                    match &step.result {
+                        Ok(()) => {
+                            ui.label(skill_name);
+                            ui.colored_label(Color32::GREEN, "OK");
+                        }
+                        Err(error) => {
+                            ui.colored_label(Color32::RED, skill_name);
+                            ui.colored_label(Color32::RED, error.to_string());
+                        }
+                    }
+                    ui.label(format!("{:.1}%", state.enemies_hp_percent()));
+                    ui.label(format!("{} / {}", state.player.hp, state.player.max_hp));
+                    ui.label(format!("{} / {}", state.player.mp, state.player.max_mp));
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -1,12 +1,20 @@
+use crate::ai::types::objective::Objective;
 use crate::game::entities::armors::Armor;
 use crate::game::entities::enemies::Challenge;
 use crate::game::types::gear::Slot;
-use crate::gui::app::AppView;
+use crate::gui::app::{App, AppView};
+use crate::gui::build_code::{Build, ImportError, ItemProblem};
+use crate::gui::build_summary::BuildSummary;
 use crate::gui::gear::GearSet;
-use crate::gui::options::{AIOptions, GameOptions, GearOptions};
+use crate::gui::options::{
+    AIOptions, GameOptions, GearOptions, ALTERNATIVES_RANGE, DEADLINE_RANGE, DEPTH_RANGE,
+    MAX_USES_RANGE, MIN_MP_RANGE, RECORD_NODES_RANGE, SAMPLES_RANGE, THREADS_RANGE,
+};
+use crate::gui::stats_handler::{StatsHandler, MAX_STAT, STATS};
 use crate::gui::widgets::number_input::unsigned_number_input;
-use egui::{Ui, Vec2};
+use egui::{Color32, Ui, Vec2};
 use std::cell::RefCell;
+use std::hash::Hash;
 use std::rc::Rc;
 
 pub struct SidePanelView {
@@ -18,6 +26,11 @@ pub struct SidePanelView {
     pub change_view: Option<AppView>,
 
     new_build_name: String,
+
+    // The build whose share code is shown
+    shared_build: Option<Build>,
+    import_text: String,
+    import_result: Option<Result<Vec<ItemProblem>, ImportError>>,
 }
 
 impl SidePanelView {
@@ -33,6 +46,9 @@ impl SidePanelView {
             view: AppView::default(),
             change_view: None,
             new_build_name: String::new(),
+            shared_build: None,
+            import_text: String::new(),
+            import_result: None,
         }
     }
 
@@ -51,6 +67,9 @@ impl SidePanelView {
                         gear_options.current_set_index = Some(index);
                         self.change_view = Some(AppView::Gear);
                     }
+                    if ui.button("Share").clicked() {
+                        self.shared_build = Some(Build::new(set, &self.game_options.borrow()));
+                    }
                     if ui.button("ｘ").clicked() {
                         gear_options.gear_sets.remove(index);
 
@@ -85,6 +104,195 @@ impl SidePanelView {
                 gear_options.current_set_index = Some(gear_options.gear_sets.len() - 1);
             }
         });
+
+        drop(gear_options);
+        self.draw_shared_build(ui);
+        ui.collapsing("Import Build", |ui| self.draw_import(ui));
+    }
+
+    fn draw_shared_build(&mut self, ui: &mut Ui) {
+        let Some(build) = &self.shared_build else {
+            return;
+        };
+
+        ui.separator();
+        ui.label(format!("Share \"{}\"", build.gear_set.name));
+        ui.label("Includes the current stats, level and armor");
+        let mut closed = false;
+        ui.horizontal(|ui| {
+            if ui.button("Copy Code").clicked() {
+                ui.output_mut(|o| o.copied_text = build.to_code());
+            }
+            if ui.button("Copy JSON").clicked() {
+                ui.output_mut(|o| o.copied_text = build.to_json());
+            }
+            closed = ui.button("Close").clicked();
+        });
+        if closed {
+            self.shared_build = None;
+        }
+    }
+
+    fn draw_import(&mut self, ui: &mut Ui) {
+        ui.add(
+            egui::TextEdit::multiline(&mut self.import_text)
+                .hint_text("Paste a build code or JSON")
+                .desired_rows(2),
+        );
+        if ui
+            .add_enabled(
+                !self.import_text.trim().is_empty(),
+                egui::Button::new("Import"),
+            )
+            .clicked()
+        {
+            self.import_result = Some(self.import_build());
+        }
+
+        match &self.import_result {
+            Some(Ok(problems)) => {
+                ui.label("Imported the build, with its stats, level and armor");
+                for problem in problems {
+                    ui.colored_label(Color32::YELLOW, problem.to_string());
+                }
+            }
+            Some(Err(error)) => {
+                ui.colored_label(Color32::RED, error.to_string());
+            }
+            None => {}
+        }
+    }
+
+    fn import_build(&mut self) -> Result<Vec<ItemProblem>, ImportError> {
+        let build = Build::parse(&self.import_text)?;
+        let (gear_set, problems) = build.restore_gear_set(&App::get_gear());
+
+        self.gear_options.borrow_mut().add_gear_set(gear_set);
+        build.apply_options(&mut self.game_options.borrow_mut());
+        self.import_text.clear();
+
+        Ok(problems)
+    }
+
+    fn skill_names(game_options: &GameOptions) -> Vec<String> {
+        game_options
+            .create_game()
+            .player
+            .get_base_type()
+            .as_player()
+            .skills
+            .iter()
+            .map(|skill| skill.name.clone())
+            .collect()
+    }
+
+    fn skill_combo_box(ui: &mut Ui, id: impl Hash, skill: &mut String, skill_names: &[String]) {
+        egui::ComboBox::new(id, "")
+            .selected_text(skill.as_str())
+            .show_ui(ui, |ui| {
+                for name in skill_names {
+                    ui.selectable_value(skill, name.clone(), name);
+                }
+            });
+    }
+
+    // Draws editable rows of a skill and a number
+    fn draw_skill_numbers(
+        ui: &mut Ui,
+        id: &str,
+        rows: &mut Vec<(String, String)>,
+        skill_names: &[String],
+        max: u32,
+        min: u32,
+    ) {
+        let mut removed_row = None;
+        egui::Grid::new(id).show(ui, |ui| {
+            for (index, (skill, number)) in rows.iter_mut().enumerate() {
+                Self::skill_combo_box(ui, (id, index), skill, skill_names);
+                unsigned_number_input(ui, number, max, min);
+                if ui.button("ｘ").clicked() {
+                    removed_row = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(index) = removed_row {
+            rows.remove(index);
+        }
+        if ui.button("+").clicked() {
+            rows.push((skill_names[0].clone(), min.max(1).to_string()));
+        }
+    }
+
+    fn draw_constraints(ui: &mut Ui, ai_options: &mut AIOptions, skill_names: &[String]) {
+        ui.label("Opener");
+        let mut removed_skill = None;
+        egui::Grid::new("side_panel_opener_grid").show(ui, |ui| {
+            for (index, skill) in ai_options.opener.iter_mut().enumerate() {
+                ui.label(format!("{}.", index + 1));
+                Self::skill_combo_box(ui, ("side_panel_opener", index), skill, skill_names);
+                if ui.button("ｘ").clicked() {
+                    removed_skill = Some(index);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(index) = removed_skill {
+            ai_options.opener.remove(index);
+        }
+        if ui.button("+").clicked() {
+            ai_options.opener.push(skill_names[0].clone());
+        }
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Min MP");
+            unsigned_number_input(
+                ui,
+                &mut ai_options.min_mp,
+                *MIN_MP_RANGE.end(),
+                *MIN_MP_RANGE.start(),
+            );
+        });
+
+        ui.collapsing("Forbidden Skills", |ui| {
+            for name in skill_names {
+                let mut forbidden = ai_options.forbidden_skills.contains(name);
+                if ui.checkbox(&mut forbidden, name).changed() {
+                    if forbidden {
+                        ai_options.forbidden_skills.push(name.clone());
+                    } else {
+                        ai_options.forbidden_skills.retain(|skill| skill != name);
+                    }
+                }
+            }
+        });
+
+        ui.separator();
+
+        ui.label("Max Uses");
+        Self::draw_skill_numbers(
+            ui,
+            "side_panel_max_uses",
+            &mut ai_options.max_uses,
+            skill_names,
+            *MAX_USES_RANGE.end(),
+            *MAX_USES_RANGE.start(),
+        );
+
+        ui.separator();
+
+        ui.label("Use Before Turn");
+        Self::draw_skill_numbers(
+            ui,
+            "side_panel_deadlines",
+            &mut ai_options.deadlines,
+            skill_names,
+            *DEADLINE_RANGE.end(),
+            *DEADLINE_RANGE.start(),
+        );
     }
 
     pub fn draw(&mut self, ui: &mut Ui) {
@@ -136,7 +344,13 @@ impl SidePanelView {
 
             ui.separator();
 
-            gear_options.get_current_set().unwrap().draw(ui);
+            let gear_set = gear_options.get_current_set().unwrap();
+            gear_set.draw(ui);
+
+            ui.separator();
+            ui.collapsing("Summary", |ui| {
+                BuildSummary::new(&self.game_options.borrow(), gear_set).draw(ui);
+            });
 
             return;
         }
@@ -178,19 +392,28 @@ impl SidePanelView {
                 egui::Grid::new("side_panel_stats_grid")
                     .num_columns(2)
                     .show(ui, |ui| {
-                        for stat in ["STR", "DEX", "INT", "CHA", "LUK", "END", "WIS"] {
+                        for stat in STATS {
                             ui.label(stat);
 
                             unsigned_number_input(
                                 ui,
                                 game_options.stats.map.get_mut(stat).unwrap(),
-                                200,
+                                MAX_STAT,
                                 0,
                             );
 
                             ui.end_row();
                         }
                     });
+
+                ui.label(format!(
+                    "{} / {} points",
+                    game_options.stats.total(),
+                    StatsHandler::budget(game_options.level)
+                ));
+                if let Err(error) = game_options.stats.validate(game_options.level) {
+                    ui.colored_label(Color32::RED, error.to_string());
+                }
             });
 
             drop(game_options);
@@ -206,7 +429,69 @@ impl SidePanelView {
             ui.collapsing("AI Options", |ui| {
                 ui.horizontal(|ui| {
                     ui.label("Depth");
-                    unsigned_number_input(ui, &mut ai_options.depth, 10, 2);
+                    unsigned_number_input(
+                        ui,
+                        &mut ai_options.depth,
+                        *DEPTH_RANGE.end(),
+                        *DEPTH_RANGE.start(),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Alternatives");
+                    unsigned_number_input(
+                        ui,
+                        &mut ai_options.alternatives,
+                        *ALTERNATIVES_RANGE.end(),
+                        *ALTERNATIVES_RANGE.start(),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Objective");
+                    egui::ComboBox::new("side_panel_objective_combo_box", "")
+                        .selected_text(ai_options.objective.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in Objective::vec() {
+                                ui.selectable_value(
+                                    &mut ai_options.objective,
+                                    option,
+                                    option.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Threads");
+                    unsigned_number_input(
+                        ui,
+                        &mut ai_options.threads,
+                        *THREADS_RANGE.end(),
+                        *THREADS_RANGE.start(),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Recorded Nodes");
+                    unsigned_number_input(
+                        ui,
+                        &mut ai_options.record_nodes,
+                        *RECORD_NODES_RANGE.end(),
+                        *RECORD_NODES_RANGE.start(),
+                    );
+                });
+                if ai_options.objective == Objective::WinProbability {
+                    ui.horizontal(|ui| {
+                        ui.label("Samples");
+                        unsigned_number_input(
+                            ui,
+                            &mut ai_options.samples,
+                            *SAMPLES_RANGE.end(),
+                            *SAMPLES_RANGE.start(),
+                        );
+                    });
+                }
+
+                ui.collapsing("Rotation Constraints", |ui| {
+                    let skill_names = Self::skill_names(&self.game_options.borrow());
+                    Self::draw_constraints(ui, &mut ai_options, &skill_names);
                 });
             });
         }
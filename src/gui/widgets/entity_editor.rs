@@ -0,0 +1,362 @@
+use crate::game::entities::entity::{EntityMutRef, EntityRef};
+use crate::game::game_manager::GameManager;
+use crate::game::types::counter::Counter;
+use crate::game::types::dict::Dict;
+use crate::game::types::effects::{Effect, Stun};
+use crate::gui::widgets::number_input::unsigned_number_input;
+use egui::{Color32, Ui};
+use std::str::FromStr;
+
+// The number in the text of an input, or `current` if the text isn't a number.
+// Inputs are only sanitized when they lose focus, so the text can be anything.
+fn parse_input<T: FromStr + Ord>(text: &str, current: T, min: T, max: T) -> T {
+    text.trim()
+        .parse()
+        .map_or(current, |value: T| value.clamp(min, max))
+}
+
+// An effect of the entity, or one that will be added to it
+struct EffectInput {
+    effect: Effect,
+    duration: String,
+    removed: bool,
+
+    // Whether the effect isn't on the entity yet
+    added: bool,
+}
+
+// The form of a new effect
+struct NewEffect {
+    name: String,
+    duration: String,
+    bonuses: Vec<(String, String)>,
+    resists: Vec<(String, String)>,
+    stun: bool,
+    death_proof: bool,
+}
+
+impl Default for NewEffect {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            duration: "1".to_string(),
+            bonuses: Vec::new(),
+            resists: Vec::new(),
+            stun: false,
+            death_proof: false,
+        }
+    }
+}
+
+impl NewEffect {
+    // Rows with an empty name or a value that isn't a number are ignored
+    fn create_dict(rows: &[(String, String)]) -> Option<Dict> {
+        let mut dict = Dict::new();
+        for (name, value) in rows {
+            if let Ok(value) = value.trim().parse() {
+                if !name.trim().is_empty() {
+                    dict.set(name.trim(), value);
+                }
+            }
+        }
+
+        if dict.is_empty() {
+            None
+        } else {
+            Some(dict)
+        }
+    }
+
+    fn create_effect(&self) -> Effect {
+        Effect::new(
+            self.name.trim(),
+            None,
+            parse_input(&self.duration, 1, 1, i8::MAX),
+            Self::create_dict(&self.bonuses),
+            Self::create_dict(&self.resists),
+            None,
+            // The user already knows the stun landed
+            self.stun.then_some(Stun::Automatic),
+            self.death_proof,
+        )
+    }
+}
+
+// Edits the state of an entity in the middle of a fight.
+// Changes are only applied to the game when `draw` returns `EditorAction::Apply`.
+pub struct EntityEditor {
+    pub index: usize,
+    name: String,
+
+    hp: String,
+    max_hp: i32,
+    mp: String,
+    max_mp: i32,
+
+    // The player's skills, with their max cooldowns.
+    // Using a skill sets one more turn than its cooldown, but that turn ends right away,
+    // so between turns a skill has at most `Skill::cooldown` turns left.
+    cooldowns: Vec<(String, String, i32)>,
+
+    counters: Vec<(Counter, String)>,
+
+    effects: Vec<EffectInput>,
+    new_effect: NewEffect,
+}
+
+pub enum EditorAction {
+    Apply,
+    Close,
+}
+
+impl EntityEditor {
+    // `index` is the index of the entity in `GameManager::get_entity`
+    pub fn new(game: &GameManager, index: usize) -> Self {
+        let entity_trait = game.get_entity_trait(index);
+        let entity = entity_trait.get_base_entity();
+
+        let cooldowns = match entity_trait.get_base_type() {
+            EntityRef::PlayerRef(player) => player
+                .skills
+                .iter()
+                .map(|skill| {
+                    (
+                        skill.name.clone(),
+                        skill.current_cooldown.to_string(),
+                        skill.cooldown,
+                    )
+                })
+                .collect(),
+            EntityRef::EnemyRef(_) => Vec::new(),
+        };
+
+        Self {
+            index,
+            name: entity.name.clone(),
+            hp: entity.hp.max(0).to_string(),
+            max_hp: entity.max_hp,
+            mp: entity.mp.max(0).to_string(),
+            max_mp: entity.max_mp,
+            cooldowns,
+            counters: entity_trait
+                .get_counters()
+                .into_iter()
+                .map(|counter| {
+                    let value = counter.value.to_string();
+                    (counter, value)
+                })
+                .collect(),
+            effects: entity
+                .effects
+                .iter()
+                .map(|effect| EffectInput {
+                    effect: effect.clone(),
+                    duration: effect.duration.to_string(),
+                    removed: false,
+                    added: false,
+                })
+                .collect(),
+            new_effect: NewEffect::default(),
+        }
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui) -> Option<EditorAction> {
+        ui.heading(format!("Edit {}", self.name));
+
+        egui::Grid::new("entity_editor_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(format!("HP (max {})", self.max_hp));
+                unsigned_number_input(ui, &mut self.hp, self.max_hp as u32, 0);
+                ui.end_row();
+
+                ui.label(format!("MP (max {})", self.max_mp));
+                unsigned_number_input(ui, &mut self.mp, self.max_mp as u32, 0);
+                ui.end_row();
+
+                for (counter, value) in &mut self.counters {
+                    ui.label(&counter.name);
+                    if counter.is_flag() {
+                        let mut on = value == "1";
+                        if ui.checkbox(&mut on, "").changed() {
+                            *value = (on as i32).to_string();
+                        }
+                    } else {
+                        unsigned_number_input(ui, value, counter.max as u32, 0);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if !self.cooldowns.is_empty() {
+            ui.collapsing("Cooldowns", |ui| {
+                egui::Grid::new("entity_editor_cooldowns_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (name, cooldown, max) in &mut self.cooldowns {
+                            ui.label(format!("{} (max {})", name, max));
+                            unsigned_number_input(ui, cooldown, *max as u32, 0);
+                            ui.end_row();
+                        }
+                    });
+            });
+        }
+
+        ui.collapsing("Effects", |ui| {
+            egui::Grid::new("entity_editor_effects_grid")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for input in &mut self.effects {
+                        if input.removed {
+                            ui.colored_label(Color32::RED, &input.effect.name);
+                            ui.label("Removed");
+                            if ui.button("Undo").clicked() {
+                                input.removed = false;
+                            }
+                        } else {
+                            ui.label(&input.effect.name);
+                            unsigned_number_input(ui, &mut input.duration, i8::MAX as u32, 1);
+                            if ui.button("Remove").clicked() {
+                                input.removed = true;
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+            ui.collapsing("Add Effect", |ui| self.draw_new_effect(ui));
+        });
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                action = Some(EditorAction::Apply);
+            }
+            if ui.button("Close").clicked() {
+                action = Some(EditorAction::Close);
+            }
+        });
+
+        action
+    }
+
+    fn draw_new_effect(&mut self, ui: &mut Ui) {
+        let new_effect = &mut self.new_effect;
+
+        egui::Grid::new("entity_editor_new_effect_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Name");
+                // Effects with the same name replace each other, so this also recreates armor effects
+                ui.add(egui::TextEdit::singleline(&mut new_effect.name).hint_text("Opening"));
+                ui.end_row();
+
+                ui.label("Duration");
+                unsigned_number_input(ui, &mut new_effect.duration, i8::MAX as u32, 1);
+                ui.end_row();
+
+                ui.label("Stun");
+                ui.checkbox(&mut new_effect.stun, "");
+                ui.end_row();
+
+                ui.label("Deathproof");
+                ui.checkbox(&mut new_effect.death_proof, "");
+                ui.end_row();
+            });
+
+        Self::draw_dict_rows(
+            ui,
+            "entity_editor_new_effect_bonuses",
+            "Bonuses",
+            &mut new_effect.bonuses,
+        );
+        Self::draw_dict_rows(
+            ui,
+            "entity_editor_new_effect_resists",
+            "Resists",
+            &mut new_effect.resists,
+        );
+
+        if ui
+            .add_enabled(!new_effect.name.trim().is_empty(), egui::Button::new("Add"))
+            .clicked()
+        {
+            let effect = new_effect.create_effect();
+            self.effects.push(EffectInput {
+                duration: effect.duration.to_string(),
+                effect,
+                removed: false,
+                added: true,
+            });
+            self.new_effect = NewEffect::default();
+        }
+    }
+
+    // Draws editable rows of a name and a value, such as `crit` and `20`
+    fn draw_dict_rows(ui: &mut Ui, id: &str, label: &str, rows: &mut Vec<(String, String)>) {
+        ui.label(label);
+        egui::Grid::new(id).num_columns(3).show(ui, |ui| {
+            let mut removed_row = None;
+            for (index, (name, value)) in rows.iter_mut().enumerate() {
+                ui.add(
+                    egui::TextEdit::singleline(name)
+                        .hint_text("crit")
+                        .desired_width(100.0),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(value)
+                        .hint_text("20")
+                        .desired_width(50.0),
+                );
+                if ui.button("-").clicked() {
+                    removed_row = Some(index);
+                }
+                ui.end_row();
+            }
+            if let Some(index) = removed_row {
+                rows.remove(index);
+            }
+        });
+        if ui.button("+").clicked() {
+            rows.push((String::new(), String::new()));
+        }
+    }
+
+    pub fn apply(&self, game: &mut GameManager) {
+        let entity_trait = game.get_entity_trait_mut(self.index);
+
+        for (counter, value) in &self.counters {
+            let value = parse_input(value, counter.value, 0, counter.max);
+            entity_trait.set_counter(&counter.name, value);
+        }
+
+        if let EntityMutRef::PlayerMutRef(player) = entity_trait.get_base_type_mut() {
+            for (skill, (_, cooldown, max)) in player.skills.iter_mut().zip(&self.cooldowns) {
+                skill.current_cooldown = parse_input(cooldown, skill.current_cooldown, 0, *max);
+            }
+        }
+
+        let entity = entity_trait.get_base_entity_mut();
+        entity.hp = parse_input(&self.hp, entity.hp, 0, self.max_hp);
+        entity.mp = parse_input(&self.mp, entity.mp, 0, self.max_mp);
+
+        // The existing effects come first, in the same order as on the entity
+        let existing_effects = self.effects.iter().filter(|input| !input.added).count();
+        for index in (0..existing_effects).rev() {
+            let input = &self.effects[index];
+            if input.removed {
+                entity.remove_effect(index);
+            } else {
+                let effect = &mut entity.effects[index];
+                effect.duration = parse_input(&input.duration, effect.duration, 1, i8::MAX);
+            }
+        }
+        for input in self.effects.iter().skip(existing_effects) {
+            if !input.removed {
+                let mut effect = input.effect.clone();
+                effect.duration = parse_input(&input.duration, effect.duration, 1, i8::MAX);
+                entity.add_effect(effect);
+            }
+        }
+    }
+}
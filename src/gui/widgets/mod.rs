@@ -1,2 +1,3 @@
+pub mod entity_editor;
 pub mod number_input;
 pub mod optional_switch;
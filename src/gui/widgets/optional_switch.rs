@@ -1,6 +1,6 @@
 use egui::{Color32, Response, Ui};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum OptionalBool {
     Some(bool),
     None(bool),
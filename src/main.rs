@@ -3,5 +3,12 @@ mod game;
 mod gui;
 
 fn main() {
+    // On the web, the AI workers load this binary as well, and shouldn't start the GUI
+    #[cfg(target_arch = "wasm32")]
+    if ai::ai_worker::is_worker() {
+        ai::ai_worker::start_worker();
+        return;
+    }
+
     gui::gui_manager::start();
 }